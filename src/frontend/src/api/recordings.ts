@@ -6,7 +6,7 @@ export const recordingsApi = {
     apiClient.post(`/api/v1/recordings/${stream_id}/start`, data),
   
   stop: (stream_id: string) =>
-    apiClient.post(`/api/v1/recordings/${stream_id}/stop`),
+    apiClient.post(`/api/v1/streams/${stream_id}/recordings/stop`),
   
   list: (stream_id?: string): Promise<{ data: Recording[] }> =>
     stream_id
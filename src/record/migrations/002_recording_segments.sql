@@ -0,0 +1,20 @@
+-- Segment settings for segmented recordings (NULL for single-file recordings)
+ALTER TABLE recordings
+    ADD COLUMN segment_duration_seconds BIGINT,
+    ADD COLUMN segment_max_size_bytes BIGINT;
+
+-- Create recording segments table
+CREATE TABLE recording_segments (
+    id UUID PRIMARY KEY,
+    recording_id UUID NOT NULL REFERENCES recordings (id) ON DELETE CASCADE,
+    segment_index INTEGER NOT NULL,
+    file_name TEXT NOT NULL,
+    file_path TEXT NOT NULL UNIQUE,
+    start_time TIMESTAMPTZ NOT NULL,
+    end_time TIMESTAMPTZ,
+    duration_seconds BIGINT,
+    file_size_bytes BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (recording_id, segment_index)
+);
@@ -0,0 +1,6 @@
+-- Create custom enum type for recording container format
+CREATE TYPE recording_container AS ENUM ('MP4', 'FMP4', 'MKV');
+
+-- Container format of each recording (existing recordings are plain MP4)
+ALTER TABLE recordings
+    ADD COLUMN container recording_container NOT NULL DEFAULT 'MP4';
@@ -0,0 +1,3 @@
+-- Length of the pre-event buffer included before start_time (milliseconds)
+ALTER TABLE recordings
+    ADD COLUMN preroll_ms BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,9 @@
+-- Create custom enum type for why a recording was stopped
+CREATE TYPE recording_stop_reason AS ENUM ('MANUAL', 'DURATION_LIMIT', 'SIZE_LIMIT', 'SCHEDULE', 'STREAM_LOST');
+
+-- Automatic stop conditions requested at start, and the reason the recording ended
+ALTER TABLE recordings
+    ADD COLUMN max_duration_seconds BIGINT,
+    ADD COLUMN max_size_bytes BIGINT,
+    ADD COLUMN stop_at TIMESTAMPTZ,
+    ADD COLUMN stop_reason recording_stop_reason;
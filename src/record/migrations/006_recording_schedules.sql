@@ -0,0 +1,33 @@
+-- Create custom enum type for schedule kinds
+CREATE TYPE schedule_kind AS ENUM ('WEEKLY', 'CRON');
+
+-- Recording schedules bound to a stream profile
+-- WEEKLY: weekdays (ISO 1=Mon .. 7=Sun) with start_time/end_time in the schedule's timezone
+--         (end_time <= start_time means the window ends on the next day)
+-- CRON:   a recording of duration_seconds starts at every occurrence of cron_expression
+CREATE TABLE recording_schedules (
+    id UUID PRIMARY KEY,
+    profile_id UUID NOT NULL,
+    name TEXT NOT NULL,
+    kind schedule_kind NOT NULL,
+    weekdays SMALLINT[] NOT NULL DEFAULT '{}',
+    start_time TIME,
+    end_time TIME,
+    cron_expression TEXT,
+    duration_seconds BIGINT,
+    timezone TEXT NOT NULL DEFAULT 'UTC',
+    label TEXT,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    -- Start a window that was already in progress when the service (re)started
+    catch_up BOOLEAN NOT NULL DEFAULT TRUE,
+    -- Start of the last window the scheduler handled (started or skipped)
+    last_window_start TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (
+        (kind = 'WEEKLY' AND start_time IS NOT NULL AND end_time IS NOT NULL)
+        OR (kind = 'CRON' AND cron_expression IS NOT NULL AND duration_seconds > 0)
+    )
+);
+
+CREATE INDEX idx_recording_schedules_profile_id ON recording_schedules (profile_id);
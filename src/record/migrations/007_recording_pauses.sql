@@ -0,0 +1,10 @@
+-- Pause intervals of recordings (resumed_at is NULL while paused)
+CREATE TABLE recording_pauses (
+    id UUID PRIMARY KEY,
+    recording_id UUID NOT NULL REFERENCES recordings (id) ON DELETE CASCADE,
+    paused_at TIMESTAMPTZ NOT NULL,
+    resumed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_recording_pauses_recording_id ON recording_pauses (recording_id, paused_at);
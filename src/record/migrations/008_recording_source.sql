@@ -0,0 +1,11 @@
+-- Source stream and negotiated media parameters captured when the recording started
+ALTER TABLE recordings
+    ADD COLUMN stream_id TEXT,
+    ADD COLUMN protocol TEXT,
+    ADD COLUMN source_url TEXT,
+    ADD COLUMN codec TEXT,
+    ADD COLUMN width INTEGER,
+    ADD COLUMN height INTEGER,
+    ADD COLUMN framerate DOUBLE PRECISION;
+
+CREATE INDEX idx_recordings_stream_id ON recordings (stream_id, start_time DESC);
@@ -0,0 +1,27 @@
+-- Create custom enum type for the RTSP lower transport
+CREATE TYPE stream_transport AS ENUM ('AUTO', 'TCP', 'UDP', 'MULTICAST');
+
+-- Stream profiles: saved camera connection settings and recording defaults.
+-- Profiles with auto_connect are connected on startup using the profile id as stream id.
+-- Passwords are only stored encrypted (see 010_stream_credentials.sql).
+CREATE TABLE stream_profiles (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    protocol TEXT NOT NULL,
+    url TEXT NOT NULL,
+    username TEXT,
+    latency_ms INTEGER NOT NULL DEFAULT 0 CHECK (latency_ms >= 0),
+    transport stream_transport NOT NULL DEFAULT 'AUTO',
+    auto_connect BOOLEAN NOT NULL DEFAULT FALSE,
+    preroll_seconds BIGINT CHECK (preroll_seconds >= 0),
+    container recording_container,
+    segment_duration_seconds BIGINT CHECK (segment_duration_seconds > 0),
+    segment_max_size_bytes BIGINT CHECK (segment_max_size_bytes > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Recording schedules are bound to a stream profile
+ALTER TABLE recording_schedules
+    ADD CONSTRAINT recording_schedules_profile_id_fkey
+    FOREIGN KEY (profile_id) REFERENCES stream_profiles (id) ON DELETE CASCADE;
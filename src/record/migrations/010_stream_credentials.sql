@@ -0,0 +1,8 @@
+-- Profile passwords are stored encrypted with the configured credentials key
+-- ("enc:v1:" followed by the Base64 nonce and ciphertext).
+ALTER TABLE stream_profiles
+    ADD COLUMN password_encrypted TEXT;
+
+-- RTSP connection timeout (seconds); NULL uses the built-in defaults
+ALTER TABLE stream_profiles
+    ADD COLUMN timeout_seconds BIGINT CHECK (timeout_seconds > 0);
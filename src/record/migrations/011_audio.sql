@@ -0,0 +1,5 @@
+-- Audio codec muxed into the recording (NULL when the recording has no audio track)
+ALTER TABLE recordings ADD COLUMN audio_codec TEXT;
+
+-- Whether the profile's stream keeps its audio track; NULL uses the configured default
+ALTER TABLE stream_profiles ADD COLUMN audio BOOLEAN;
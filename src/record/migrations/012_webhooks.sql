@@ -0,0 +1,39 @@
+-- Outgoing webhook subscriptions
+-- event_types: recording.completed, recording.failed, stream.connected, stream.lost
+CREATE TABLE webhooks (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    -- HMAC-SHA256 signing secret, encrypted with the configured credentials key
+    secret_encrypted TEXT,
+    event_types TEXT[] NOT NULL,
+    -- Only deliver events of this stream (NULL: all streams)
+    stream_id TEXT,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Create custom enum type for delivery status
+CREATE TYPE webhook_delivery_status AS ENUM ('PENDING', 'SUCCEEDED', 'FAILED');
+
+-- Delivery log: one row per event and webhook, updated after every attempt
+CREATE TABLE webhook_deliveries (
+    id UUID PRIMARY KEY,
+    webhook_id UUID NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+    event_type TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    status webhook_delivery_status NOT NULL DEFAULT 'PENDING',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    -- HTTP status and error of the last attempt
+    response_status INTEGER,
+    last_error TEXT,
+    -- When the next attempt is due (NULL once the delivery succeeded or failed)
+    next_attempt_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_webhook_deliveries_webhook_id ON webhook_deliveries (webhook_id, created_at DESC);
+CREATE INDEX idx_webhook_deliveries_pending ON webhook_deliveries (next_attempt_at)
+    WHERE status = 'PENDING';
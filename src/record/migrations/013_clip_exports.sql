@@ -0,0 +1,25 @@
+-- Recording a clip was exported from (NULL for recordings captured from a stream)
+ALTER TABLE recordings
+    ADD COLUMN parent_recording_id UUID REFERENCES recordings (id) ON DELETE SET NULL;
+
+-- Create custom enum types for clip export jobs
+CREATE TYPE clip_export_mode AS ENUM ('COPY', 'REENCODE');
+CREATE TYPE clip_export_status AS ENUM ('PENDING', 'RUNNING', 'COMPLETED', 'FAILED');
+
+-- Clip export jobs (offsets are relative to the start of the source file, including pre-roll)
+CREATE TABLE clip_exports (
+    id UUID PRIMARY KEY,
+    recording_id UUID NOT NULL REFERENCES recordings (id) ON DELETE CASCADE,
+    clip_recording_id UUID REFERENCES recordings (id) ON DELETE SET NULL,
+    start_offset_ms BIGINT NOT NULL,
+    end_offset_ms BIGINT NOT NULL,
+    mode clip_export_mode NOT NULL,
+    status clip_export_status NOT NULL,
+    progress DOUBLE PRECISION NOT NULL DEFAULT 0,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_clip_exports_recording_id ON clip_exports (recording_id, created_at DESC);
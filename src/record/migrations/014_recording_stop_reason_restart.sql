@@ -0,0 +1,2 @@
+-- Recordings left in RECORDING after an unclean shutdown and recovered on startup
+ALTER TYPE recording_stop_reason ADD VALUE 'SERVICE_RESTART';
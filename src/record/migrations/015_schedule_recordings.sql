@@ -0,0 +1,4 @@
+-- Recording the scheduler started for last_window_start. When it ends before the window
+-- does (service restart, lost stream), the scheduler starts a new one for the rest of the window.
+ALTER TABLE recording_schedules
+    ADD COLUMN last_recording_id UUID REFERENCES recordings (id) ON DELETE SET NULL;
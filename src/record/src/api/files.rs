@@ -0,0 +1,369 @@
+use crate::error::RecordError;
+use axum::{
+    body::Body,
+    http::{
+        header::{
+            ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+            IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE,
+        },
+        HeaderMap, HeaderValue, Method, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use std::io::SeekFrom;
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+/// Content-Dispositionの種類（ダウンロードまたはブラウザでの再生）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposition {
+    Attachment,
+    Inline,
+}
+
+impl Disposition {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Disposition::Attachment => "attachment",
+            Disposition::Inline => "inline",
+        }
+    }
+}
+
+/// ファイルを返す。Range（単一の範囲）、If-Range、ETag / Last-Modifiedによる条件付きリクエストとHEADに対応する
+pub async fn serve_file(
+    file_path: &Path,
+    file_name: &str,
+    content_type: &str,
+    disposition: Disposition,
+    method: &Method,
+    request_headers: &HeaderMap,
+) -> Result<Response, RecordError> {
+    let mut file = tokio::fs::File::open(file_path).await?;
+    let metadata = file.metadata().await?;
+    let length = metadata.len();
+    let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+    // 録画中のファイルはサイズが変わるため、サイズと更新時刻から生成する
+    let etag = format!(
+        "\"{:x}-{:x}\"",
+        length,
+        modified.map(|m| m.timestamp_micros()).unwrap_or_default()
+    );
+
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    insert_header(&mut headers, ETAG, &etag);
+    if let Some(modified) = modified {
+        insert_header(&mut headers, LAST_MODIFIED, &http_date(modified));
+    }
+    insert_header(&mut headers, CONTENT_TYPE, content_type);
+    insert_header(
+        &mut headers,
+        CONTENT_DISPOSITION,
+        &content_disposition(disposition, file_name),
+    );
+
+    if is_not_modified(request_headers, &etag, modified) {
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+
+    // If-Rangeが一致しない場合（ファイルが変わった場合）は全体を返す
+    let range = request_headers
+        .get(RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| if_range_matches(request_headers, &etag, modified))
+        .and_then(|value| parse_range(value, length));
+    let (status, start, end) = match range {
+        None => (StatusCode::OK, 0, length),
+        Some(Ok((start, end))) => {
+            insert_header(
+                &mut headers,
+                CONTENT_RANGE,
+                &format!("bytes {}-{}/{}", start, end - 1, length),
+            );
+            (StatusCode::PARTIAL_CONTENT, start, end)
+        }
+        Some(Err(())) => {
+            insert_header(&mut headers, CONTENT_RANGE, &format!("bytes */{}", length));
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response());
+        }
+    };
+    headers.insert(CONTENT_LENGTH, HeaderValue::from(end - start));
+
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        file.seek(SeekFrom::Start(start)).await?;
+        Body::from_stream(ReaderStream::new(file.take(end - start)))
+    };
+    Ok((status, headers, body).into_response())
+}
+
+fn insert_header(headers: &mut HeaderMap, name: axum::http::HeaderName, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        headers.insert(name, value);
+    }
+}
+
+/// If-None-Match（優先）またはIf-Modified-Sinceから304を返すか判定する
+fn is_not_modified(headers: &HeaderMap, etag: &str, modified: Option<DateTime<Utc>>) -> bool {
+    if let Some(if_none_match) = headers.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag);
+    }
+    let since = headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_http_date);
+    match (since, modified) {
+        (Some(since), Some(modified)) => modified.timestamp() <= since.timestamp(),
+        _ => false,
+    }
+}
+
+/// If-Rangeが無いか、ETag（強い比較）または更新時刻が一致する場合にtrue
+fn if_range_matches(headers: &HeaderMap, etag: &str, modified: Option<DateTime<Utc>>) -> bool {
+    let Some(if_range) = headers.get(IF_RANGE).and_then(|v| v.to_str().ok()) else {
+        return true;
+    };
+    let if_range = if_range.trim();
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        return if_range == etag;
+    }
+    match (parse_http_date(if_range), modified) {
+        (Some(date), Some(modified)) => date.timestamp() == modified.timestamp(),
+        _ => false,
+    }
+}
+
+/// Rangeヘッダー（bytes=start-end / bytes=start- / bytes=-suffix）を[start, end)に変換する。
+/// 解釈できない場合と複数の範囲はNone（全体を返す）、範囲がファイル外の場合はErr
+fn parse_range(value: &str, length: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+    if start.is_empty() {
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 || length == 0 {
+            return Some(Err(()));
+        }
+        return Some(Ok((length.saturating_sub(suffix), length)));
+    }
+    let start: u64 = start.parse().ok()?;
+    let end = if end.is_empty() {
+        length
+    } else {
+        let last: u64 = end.parse().ok()?;
+        if last < start {
+            return None;
+        }
+        last.saturating_add(1).min(length)
+    };
+    if start >= length {
+        return Some(Err(()));
+    }
+    Some(Ok((start, end)))
+}
+
+/// Content-Dispositionの値。ASCII以外を含むファイル名はfilenameを置き換えた代替名にし、
+/// 元の名前をfilename*（RFC 5987、UTF-8のパーセントエンコード）で渡す
+fn content_disposition(disposition: Disposition, file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if fallback == file_name {
+        return format!("{}; filename=\"{}\"", disposition.as_str(), file_name);
+    }
+    let encoded: String = file_name
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect();
+    format!(
+        "{}; filename=\"{}\"; filename*=UTF-8''{}",
+        disposition.as_str(),
+        fallback,
+        encoded
+    )
+}
+
+/// HTTP-date（IMF-fixdate）
+fn http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const ETAG: &str = "\"400-5f1e\"";
+
+    fn headers(name: axum::http::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        insert_header(&mut headers, name, value);
+        headers
+    }
+
+    fn modified() -> Option<DateTime<Utc>> {
+        Some(Utc.with_ymd_and_hms(2026, 10, 16, 8, 30, 0).unwrap())
+    }
+
+    #[test]
+    fn parses_closed_and_open_ended_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some(Ok((0, 100))));
+        assert_eq!(parse_range("bytes=500-", 1000), Some(Ok((500, 1000))));
+        // 終端がファイル外の場合はファイルの末尾まで
+        assert_eq!(parse_range("bytes=900-2000", 1000), Some(Ok((900, 1000))));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse_range("bytes=-100", 1000), Some(Ok((900, 1000))));
+        // ファイルより長い場合は全体
+        assert_eq!(parse_range("bytes=-5000", 1000), Some(Ok((0, 1000))));
+        assert_eq!(parse_range("bytes=-0", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=-100", 0), Some(Err(())));
+    }
+
+    #[test]
+    fn start_beyond_end_of_file_is_not_satisfiable() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=1500-1600", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=0-", 0), Some(Err(())));
+    }
+
+    #[test]
+    fn unsupported_ranges_return_whole_file() {
+        assert_eq!(parse_range("bytes=0-99,200-299", 1000), None);
+        assert_eq!(parse_range("bytes=100-50", 1000), None);
+        assert_eq!(parse_range("items=0-99", 1000), None);
+        assert_eq!(parse_range("bytes=abc-", 1000), None);
+        assert_eq!(parse_range("bytes=100", 1000), None);
+    }
+
+    #[test]
+    fn if_none_match_uses_weak_comparison() {
+        for value in [ETAG, "W/\"400-5f1e\"", "\"other\", \"400-5f1e\"", "*"] {
+            assert!(
+                is_not_modified(&headers(IF_NONE_MATCH, value), ETAG, modified()),
+                "{}",
+                value
+            );
+        }
+        assert!(!is_not_modified(
+            &headers(IF_NONE_MATCH, "\"other\""),
+            ETAG,
+            modified()
+        ));
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence_over_if_modified_since() {
+        let mut request = headers(IF_NONE_MATCH, "\"other\"");
+        insert_header(
+            &mut request,
+            IF_MODIFIED_SINCE,
+            &http_date(modified().unwrap()),
+        );
+        assert!(!is_not_modified(&request, ETAG, modified()));
+    }
+
+    #[test]
+    fn if_modified_since_compares_seconds() {
+        let date = modified().unwrap();
+        let request = headers(IF_MODIFIED_SINCE, &http_date(date));
+        assert!(is_not_modified(
+            &request,
+            ETAG,
+            Some(date + chrono::Duration::milliseconds(500))
+        ));
+        assert!(!is_not_modified(
+            &request,
+            ETAG,
+            Some(date + chrono::Duration::seconds(1))
+        ));
+        assert!(!is_not_modified(&request, ETAG, None));
+        assert!(!is_not_modified(&HeaderMap::new(), ETAG, modified()));
+    }
+
+    #[test]
+    fn if_range_uses_strong_comparison() {
+        assert!(if_range_matches(&HeaderMap::new(), ETAG, modified()));
+        assert!(if_range_matches(&headers(IF_RANGE, ETAG), ETAG, modified()));
+        // 弱いETagは一致しない
+        assert!(!if_range_matches(
+            &headers(IF_RANGE, "W/\"400-5f1e\""),
+            ETAG,
+            modified()
+        ));
+        assert!(!if_range_matches(
+            &headers(IF_RANGE, "\"other\""),
+            ETAG,
+            modified()
+        ));
+    }
+
+    #[test]
+    fn if_range_matches_exact_date() {
+        let date = modified().unwrap();
+        let request = headers(IF_RANGE, &http_date(date));
+        assert!(if_range_matches(&request, ETAG, Some(date)));
+        assert!(!if_range_matches(
+            &request,
+            ETAG,
+            Some(date + chrono::Duration::seconds(1))
+        ));
+        assert!(!if_range_matches(&request, ETAG, None));
+        assert!(!if_range_matches(
+            &headers(IF_RANGE, "not a date"),
+            ETAG,
+            modified()
+        ));
+    }
+
+    #[test]
+    fn content_disposition_encodes_non_ascii_names() {
+        assert_eq!(
+            content_disposition(Disposition::Attachment, "cam1_20261016.mp4"),
+            "attachment; filename=\"cam1_20261016.mp4\""
+        );
+        let value = content_disposition(Disposition::Inline, "玄関カメラ 朝.mp4");
+        assert_eq!(
+            value,
+            "inline; filename=\"_____ _.mp4\"; \
+             filename*=UTF-8''%E7%8E%84%E9%96%A2%E3%82%AB%E3%83%A1%E3%83%A9%20%E6%9C%9D.mp4"
+        );
+        // ヘッダーの値として有効（無効な値は送信されない）
+        assert!(HeaderValue::from_str(&value).is_ok());
+        assert_eq!(
+            content_disposition(Disposition::Attachment, "a\"b.mp4"),
+            "attachment; filename=\"a_b.mp4\"; filename*=UTF-8''a%22b.mp4"
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use crate::app::AppState;
+use crate::error::RecordError;
+use crate::models::{ClipExport, ClipExportRequest};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// 録画の一部をクリップとして書き出すジョブを登録する。書き出しはバックグラウンドで実行される
+pub async fn create(
+    State(app_state): State<Arc<AppState>>,
+    Path(recording_id): Path<Uuid>,
+    Json(request): Json<ClipExportRequest>,
+) -> Result<(StatusCode, Json<ClipExport>), RecordError> {
+    let export = app_state.clips.create(recording_id, &request).await?;
+    Ok((StatusCode::ACCEPTED, Json(export)))
+}
+
+/// 録画から書き出したクリップのジョブの一覧
+pub async fn list(
+    State(app_state): State<Arc<AppState>>,
+    Path(recording_id): Path<Uuid>,
+) -> Result<Json<Vec<ClipExport>>, RecordError> {
+    app_state.database.get_recording(recording_id).await?;
+    let exports = app_state.database.list_clip_exports(recording_id).await?;
+    Ok(Json(exports))
+}
+
+/// ジョブの状態と進捗
+pub async fn get(
+    State(app_state): State<Arc<AppState>>,
+    Path(export_id): Path<Uuid>,
+) -> Result<Json<ClipExport>, RecordError> {
+    let export = app_state.database.get_clip_export(export_id).await?;
+    Ok(Json(export))
+}
@@ -0,0 +1,87 @@
+use crate::app::AppState;
+use crate::events::Event;
+use crate::models::EventQuery;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    response::{
+        sse::{self, KeepAlive, Sse},
+        IntoResponse,
+    },
+};
+use futures::Stream;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tracing::warn;
+
+/// イベントをServer-Sent Eventsで配信する
+pub async fn sse(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<EventQuery>,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let receiver = app_state.events.subscribe();
+    let stream = futures::stream::unfold((receiver, query), |(mut receiver, query)| async move {
+        let event = next_event(&mut receiver, &query).await?;
+        let data = sse::Event::default()
+            .json_data(&event)
+            .unwrap_or_else(|_| sse::Event::default());
+        Some((Ok(data), (receiver, query)))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// イベントをWebSocketで配信する（クライアントからのメッセージは切断の検知にのみ使用する）
+pub async fn websocket(
+    ws: WebSocketUpgrade,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<EventQuery>,
+) -> impl IntoResponse {
+    let receiver = app_state.events.subscribe();
+    ws.on_upgrade(move |socket| forward_events(socket, receiver, query))
+}
+
+async fn forward_events(mut socket: WebSocket, mut receiver: Receiver<Event>, query: EventQuery) {
+    loop {
+        tokio::select! {
+            event = next_event(&mut receiver, &query) => {
+                let Some(event) = event else {
+                    break;
+                };
+                let Ok(text) = serde_json::to_string(&event) else {
+                    continue;
+                };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// 購読条件に一致する次のイベントを待つ。配信が終了した場合はNone
+async fn next_event(receiver: &mut Receiver<Event>, query: &EventQuery) -> Option<Event> {
+    loop {
+        match receiver.recv().await {
+            Ok(event) => {
+                let matches = query
+                    .stream_id
+                    .as_ref()
+                    .is_none_or(|stream_id| event.stream_id.as_ref() == Some(stream_id));
+                if matches {
+                    return Some(event);
+                }
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Event subscriber lagged, {} event(s) skipped", skipped);
+            }
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
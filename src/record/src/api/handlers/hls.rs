@@ -0,0 +1,52 @@
+use crate::app::AppState;
+use crate::error::RecordError;
+use crate::hls::hls_content_type;
+use crate::stream::StreamId;
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+/// HLSのプレイリストとセグメントを返す
+pub async fn file(
+    State(app_state): State<Arc<AppState>>,
+    Path((stream_id, file_name)): Path<(StreamId, String)>,
+) -> Result<Response, RecordError> {
+    let content_type = hls_content_type(&file_name)
+        .ok_or_else(|| RecordError::NotFound(format!("HLS file {} not found", file_name)))?;
+    let directory = app_state
+        .stream_manager
+        .hls_directory(&stream_id)
+        .await
+        .ok_or_else(|| {
+            RecordError::NotFound(format!("HLS output of stream {} not found", stream_id))
+        })?;
+    // 古いセグメントはhlscmafsinkが削除するため、取得が間に合わなかった場合も404にする
+    let body = match tokio::fs::read(directory.join(&file_name)).await {
+        Ok(body) => body,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(RecordError::NotFound(format!(
+                "HLS file {} not found",
+                file_name
+            )))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // プレイリストは更新され続けるためキャッシュさせない
+    let cache_control = if file_name.ends_with(".m3u8") {
+        HeaderValue::from_static("no-cache")
+    } else {
+        HeaderValue::from_static("max-age=60")
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static(content_type)),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        body,
+    )
+        .into_response())
+}
@@ -0,0 +1,26 @@
+use crate::app::AppState;
+use crate::error::RecordError;
+use axum::{extract::State, http::header, response::IntoResponse};
+use std::sync::Arc;
+
+/// Prometheus形式のメトリクス
+pub async fn metrics(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, RecordError> {
+    app_state.stream_manager.update_metrics().await;
+    let (connections, idle) = app_state.database.pool_status();
+    app_state
+        .metrics
+        .db_pool_connections
+        .set(connections as i64);
+    app_state.metrics.db_pool_idle_connections.set(idle as i64);
+
+    let body = app_state.metrics.render()?;
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    ))
+}
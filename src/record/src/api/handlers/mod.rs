@@ -1,6 +1,13 @@
+pub mod clips;
+pub mod events;
 pub mod health;
+pub mod hls;
+pub mod metrics;
+pub mod profiles;
 pub mod recordings;
+pub mod schedules;
 pub mod streams;
+pub mod webhooks;
 pub mod webrtcs;
 
 pub use health::*;
@@ -0,0 +1,129 @@
+use crate::app::AppState;
+use crate::error::RecordError;
+use crate::models::{ConnectResponse, StreamProfile, StreamProfileRequest};
+use crate::preroll::validate_preroll_seconds;
+use crate::stream::sanitize_url;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+pub async fn create(
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<StreamProfileRequest>,
+) -> Result<(StatusCode, Json<StreamProfile>), RecordError> {
+    validate(&request)?;
+    let password_encrypted = encrypt_password(&app_state, &request)?;
+    let profile = app_state
+        .database
+        .create_profile(&request, password_encrypted.as_deref())
+        .await?;
+    info!(
+        "Created stream profile: id={}, name={}",
+        profile.id, profile.name
+    );
+    Ok((StatusCode::CREATED, Json(profile)))
+}
+
+pub async fn list(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<StreamProfile>>, RecordError> {
+    let profiles = app_state.database.list_profiles().await?;
+    Ok(Json(profiles))
+}
+
+pub async fn get(
+    State(app_state): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+) -> Result<Json<StreamProfile>, RecordError> {
+    let profile = app_state.database.get_profile(profile_id).await?;
+    Ok(Json(profile))
+}
+
+pub async fn update(
+    State(app_state): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+    Json(request): Json<StreamProfileRequest>,
+) -> Result<Json<StreamProfile>, RecordError> {
+    validate(&request)?;
+    let password_encrypted = encrypt_password(&app_state, &request)?;
+    let profile = app_state
+        .database
+        .update_profile(profile_id, &request, password_encrypted.as_deref())
+        .await?;
+    info!("Updated stream profile: id={}", profile.id);
+    Ok(Json(profile))
+}
+
+pub async fn delete(
+    State(app_state): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+) -> Result<StatusCode, RecordError> {
+    app_state.database.delete_profile(profile_id).await?;
+    info!("Deleted stream profile: id={}", profile_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// プロファイルの設定でストリームに接続する（接続済みの場合は何もしない）
+pub async fn connect(
+    State(app_state): State<Arc<AppState>>,
+    Path(profile_id): Path<Uuid>,
+) -> Result<Json<ConnectResponse>, RecordError> {
+    let profile = app_state.database.get_profile(profile_id).await?;
+    let stream_id = app_state.connect_profile(&profile).await?;
+    Ok(Json(ConnectResponse {
+        stream_id,
+        status: "CONNECTED".to_string(),
+        message: format!("Stream connected for profile: {}", profile.name),
+    }))
+}
+
+/// リクエストのパスワードを暗号化する（空文字列はパスワードの削除としてそのまま渡す）
+fn encrypt_password(
+    app_state: &AppState,
+    request: &StreamProfileRequest,
+) -> Result<Option<String>, RecordError> {
+    match &request.password {
+        Some(password) if !password.is_empty() => app_state.credentials.encrypt(password).map(Some),
+        Some(_) => Ok(Some(String::new())),
+        None => Ok(None),
+    }
+}
+
+fn validate(request: &StreamProfileRequest) -> Result<(), RecordError> {
+    // WebRTCは配信者からの接続（WHIP）のため、プロファイルから接続できるのはRTSPのみ
+    if request.protocol != "rtsp" {
+        return Err(RecordError::StreamError(format!(
+            "Unsupported protocol: {}",
+            request.protocol
+        )));
+    }
+    // URLはそのまま保存・返却されるため、認証情報は暗号化して保存するusername/passwordで受け取る
+    if sanitize_url(&request.url) != request.url {
+        return Err(RecordError::StreamError(
+            "url must not contain credentials, use username and password instead".to_string(),
+        ));
+    }
+    if request.segment_duration_seconds == Some(0) || request.segment_max_size_bytes == Some(0) {
+        return Err(RecordError::StreamError(
+            "segment_duration_seconds and segment_max_size_bytes must be greater than 0"
+                .to_string(),
+        ));
+    }
+    if let Some(preroll_seconds) = request.preroll_seconds {
+        validate_preroll_seconds(preroll_seconds)?;
+    }
+    if request
+        .latency_ms
+        .is_some_and(|latency| latency > i32::MAX as u32)
+    {
+        return Err(RecordError::StreamError(
+            "latency_ms is out of range".to_string(),
+        ));
+    }
+    Ok(())
+}
@@ -1,180 +1,122 @@
+use crate::api::files::{serve_file, Disposition};
 use crate::app::AppState;
 use crate::error::RecordError;
 use crate::models::{
-    RecordingDetails, RecordingListItem, StartRecordingResponse, StopRecordingResponse,
+    PauseRecordingResponse, RecordingDetails, RecordingListItem, RecordingListQuery,
+    RecordingSegment, StartRecordingRequest, StartRecordingResponse, StopReason,
+    StopRecordingResponse, StopStreamRecordingResult,
 };
 use crate::stream::StreamId;
 use axum::{
-    body::Body,
-    extract::{Path, State},
-    http::{
-        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
-        StatusCode,
-    },
+    extract::{Path, Query, State},
+    http::{HeaderMap, Method, StatusCode},
     response::Response,
     Json,
 };
 use chrono::Utc;
-use std::panic::AssertUnwindSafe;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio_util::io::ReaderStream;
 use tracing::{error, info};
 use uuid::Uuid;
 
 pub async fn start(
     State(app_state): State<Arc<AppState>>,
     Path((stream_id,)): Path<(StreamId,)>,
+    request: Option<Json<StartRecordingRequest>>,
 ) -> Result<Json<StartRecordingResponse>, RecordError> {
     info!("Starting recording for stream: {}", stream_id);
-    let recording_id = Uuid::new_v4().to_string();
-    info!("[recording {}] Generated recording ID", recording_id);
-
-    let location = format!("/var/data/recordings/{}.mp4", recording_id);
-    info!(
-        "[recording {}] Recording file location: {}",
-        recording_id, location
-    );
-
-    let start_time = Utc::now();
-    let file_name = format!("{}.mp4", recording_id);
-    // DBに録画情報を登録
-    app_state
-        .database
-        .create_recording(
-            Uuid::parse_str(&recording_id).unwrap(),
-            file_name.clone(),
-            location.clone(),
-            start_time,
-        )
-        .await?;
-
-    let recording_id2 = recording_id.clone();
-    let location2 = location.clone();
-    let app_state2 = app_state.clone();
-    let stream_id2 = stream_id.clone();
-    let result = tokio::task::spawn_blocking(AssertUnwindSafe(move || {
-        futures::executor::block_on(app_state2.stream_manager.start_recording(
-            &stream_id2,
-            &recording_id2,
-            &location2,
-        ))
-    }))
-    .await;
-
-    match result {
-        Ok(Ok(_)) => {
-            info!(
-                "[recording {}] Successfully started recording for stream: {}",
-                recording_id, stream_id
-            );
-            Ok(Json(StartRecordingResponse {
-                recording_id,
-                stream_id: stream_id.clone(),
-                location,
-                message: format!("Recording started successfully for stream: {}", stream_id),
-                status: "RECORDING".to_string(),
-            }))
-        }
-        Ok(Err(e)) => {
-            error!(
-                "[recording {}] Failed to start recording for stream {}: {}",
-                recording_id, stream_id, e
-            );
-            Err(e)
-        }
-        Err(e) => {
-            error!(
-                "[recording {}] Panic occurred in start_recording for stream {}: {:?}",
-                recording_id, stream_id, e
-            );
-            Err(RecordError::StreamError(format!(
-                "Panic occurred in start_recording: {:?}",
-                e
-            )))
-        }
-    }
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let response = app_state.start_recording(&stream_id, request).await?;
+    Ok(Json(response))
 }
 
+/// 録画IDを指定して録画を停止する
 pub async fn stop(
     State(app_state): State<Arc<AppState>>,
-    Path(stream_id): Path<String>,
+    Path(recording_id): Path<Uuid>,
 ) -> Result<Json<StopRecordingResponse>, RecordError> {
-    info!("Received recording stop request for stream: {}", stream_id);
-    // Stop recording in stream manager
-    let recording_id = app_state
-        .stream_manager
-        .stop_recording(&stream_id)
-        .await
-        .map_err(|e| {
-            error!(
-                "Failed to stop recording in stream manager for stream {}: {}",
-                stream_id, e
-            );
-            e
-        })?;
-    let end_time = Utc::now();
-    info!(
-        "Getting recording details from database: id={}",
-        recording_id
-    );
-    let uuid1 =
-        Uuid::parse_str(&recording_id).map_err(|e| RecordError::StreamError(e.to_string()))?;
-    let recording = app_state.database.get_recording(uuid1).await.map_err(|e| {
-        error!("Failed to get recording details from database: {}", e);
-        e
-    })?;
-    let duration = (end_time - recording.start_time).num_seconds();
-    let file_size = match std::fs::metadata(&recording.file_path) {
-        Ok(metadata) => {
-            let size = metadata.len() as i64;
-            info!("Recording file size: {} bytes", size);
-            size
-        }
-        Err(e) => {
-            error!(
-                "Failed to get recording file metadata: {} (path={})",
-                e, recording.file_path
-            );
-            0
-        }
-    };
+    info!("Received recording stop request: {}", recording_id);
+    let response = stop_recording(&app_state, &recording_id.to_string()).await?;
+    Ok(Json(response))
+}
+
+/// ストリームで実行中の録画をすべて停止する。停止に失敗した録画があっても残りの録画の停止を続け、
+/// 録画ごとの結果を返す
+pub async fn stop_stream(
+    State(app_state): State<Arc<AppState>>,
+    Path((stream_id,)): Path<(StreamId,)>,
+) -> Result<Json<Vec<StopStreamRecordingResult>>, RecordError> {
     info!(
-        "Updating recording as completed: id={}, duration={}s, size={} bytes",
-        recording_id, duration, file_size
+        "Received stop request for recordings of stream: {}",
+        stream_id
     );
-    let uuid2 =
-        Uuid::parse_str(&recording_id).map_err(|e| RecordError::StreamError(e.to_string()))?;
-    let _updated_recording = app_state
-        .database
-        .update_recording_completed(uuid2, end_time, duration, file_size)
+    let stream = app_state
+        .stream_manager
+        .get_status(&stream_id)
+        .await
+        .ok_or_else(|| RecordError::StreamError(format!("Stream {} not found", stream_id)))?;
+    if stream.recording_ids.is_empty() {
+        return Err(RecordError::StreamError(format!(
+            "Stream {} is not recording",
+            stream_id
+        )));
+    }
+    let mut results = Vec::with_capacity(stream.recording_ids.len());
+    for recording_id in &stream.recording_ids {
+        let result = match stop_recording(&app_state, recording_id).await {
+            Ok(response) => StopStreamRecordingResult {
+                recording_id: response.recording_id,
+                status: response.status,
+                message: response.message,
+            },
+            Err(e) => StopStreamRecordingResult {
+                recording_id: recording_id.clone(),
+                status: "STOP_FAILED".to_string(),
+                message: e.to_string(),
+            },
+        };
+        results.push(result);
+    }
+    Ok(Json(results))
+}
+
+/// 録画を停止し、DBの録画を完了にする
+async fn stop_recording(
+    app_state: &AppState,
+    recording_id: &str,
+) -> Result<StopRecordingResponse, RecordError> {
+    let (stream_id, recording) = app_state
+        .stream_manager
+        .stop_recording(recording_id, StopReason::Manual)
         .await
         .map_err(|e| {
-            error!(
-                "Failed to update recording as completed: {} (recording_id={})",
-                e, recording_id
-            );
+            error!("Failed to stop recording {}: {}", recording_id, e);
             e
         })?;
     info!(
         "Successfully stopped recording with ID: {} for stream: {}",
-        recording_id, stream_id
+        recording.id, stream_id
     );
-    Ok(Json(StopRecordingResponse {
-        recording_id,
+    Ok(StopRecordingResponse {
+        recording_id: recording.id.to_string(),
         stream_id: stream_id.clone(),
+        stop_reason: StopReason::Manual,
         status: "RECORDING_STOPPED".to_string(),
         message: format!(
             "Recording has been stopped and saved for stream: {}",
             stream_id
         ),
-    }))
+    })
 }
 
 pub async fn list(
     State(app_state): State<Arc<AppState>>,
+    Query(query): Query<RecordingListQuery>,
 ) -> Result<Json<Vec<RecordingListItem>>, RecordError> {
-    let recordings = app_state.database.list_recordings().await?;
+    let recordings = app_state
+        .database
+        .list_recordings(query.stream_id.as_deref())
+        .await?;
     let items: Vec<RecordingListItem> = recordings.into_iter().map(Into::into).collect();
     Ok(Json(items))
 }
@@ -184,39 +126,234 @@ pub async fn get(
     Path(recording_id): Path<Uuid>,
 ) -> Result<Json<RecordingDetails>, RecordError> {
     let recording = app_state.database.get_recording(recording_id).await?;
-    Ok(Json(recording.into()))
+    let pauses = app_state.database.list_pauses(recording_id).await?;
+    Ok(Json(RecordingDetails::from(recording).with_pauses(pauses)))
+}
+
+pub async fn pause(
+    State(app_state): State<Arc<AppState>>,
+    Path(recording_id): Path<Uuid>,
+) -> Result<Json<PauseRecordingResponse>, RecordError> {
+    info!("Received recording pause request: {}", recording_id);
+    app_state
+        .stream_manager
+        .pause_recording(&recording_id.to_string())
+        .await?;
+    // DBに記録できなければ一時停止を取り消し、録画とDBの状態を揃える
+    if let Err(e) = app_state
+        .database
+        .create_pause(recording_id, Utc::now())
+        .await
+    {
+        if let Err(revert) = app_state
+            .stream_manager
+            .resume_recording(&recording_id.to_string())
+            .await
+        {
+            error!(
+                "[recording {}] Failed to revert pause: {}",
+                recording_id, revert
+            );
+        }
+        return Err(e);
+    }
+    Ok(Json(PauseRecordingResponse {
+        recording_id: recording_id.to_string(),
+        status: "PAUSED".to_string(),
+        message: "Recording has been paused.".to_string(),
+    }))
+}
+
+pub async fn resume(
+    State(app_state): State<Arc<AppState>>,
+    Path(recording_id): Path<Uuid>,
+) -> Result<Json<PauseRecordingResponse>, RecordError> {
+    info!("Received recording resume request: {}", recording_id);
+    app_state
+        .stream_manager
+        .resume_recording(&recording_id.to_string())
+        .await?;
+    // DBに記録できなければ一時停止に戻し、録画とDBの状態を揃える
+    if let Err(e) = app_state
+        .database
+        .close_pause(recording_id, Utc::now())
+        .await
+    {
+        if let Err(revert) = app_state
+            .stream_manager
+            .pause_recording(&recording_id.to_string())
+            .await
+        {
+            error!(
+                "[recording {}] Failed to revert resume: {}",
+                recording_id, revert
+            );
+        }
+        return Err(e);
+    }
+    Ok(Json(PauseRecordingResponse {
+        recording_id: recording_id.to_string(),
+        status: "RECORDING".to_string(),
+        message: "Recording has been resumed.".to_string(),
+    }))
+}
+
+pub async fn list_segments(
+    State(app_state): State<Arc<AppState>>,
+    Path(recording_id): Path<Uuid>,
+) -> Result<Json<Vec<RecordingSegment>>, RecordError> {
+    // 録画の存在確認
+    app_state.database.get_recording(recording_id).await?;
+    let segments = app_state.database.list_segments(recording_id).await?;
+    Ok(Json(segments))
 }
 
 pub async fn download(
     State(app_state): State<Arc<AppState>>,
     Path(recording_id): Path<Uuid>,
-) -> Result<Response<Body>, RecordError> {
+    method: Method,
+    headers: HeaderMap,
+) -> Result<Response, RecordError> {
+    recording_file(
+        &app_state,
+        recording_id,
+        Disposition::Attachment,
+        &method,
+        &headers,
+    )
+    .await
+}
+
+/// ブラウザの<video>で再生するための配信（Rangeでシークできる）
+pub async fn stream(
+    State(app_state): State<Arc<AppState>>,
+    Path(recording_id): Path<Uuid>,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<Response, RecordError> {
+    recording_file(
+        &app_state,
+        recording_id,
+        Disposition::Inline,
+        &method,
+        &headers,
+    )
+    .await
+}
+
+pub async fn download_segment(
+    State(app_state): State<Arc<AppState>>,
+    Path((recording_id, segment_index)): Path<(Uuid, i32)>,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<Response, RecordError> {
+    segment_file(
+        &app_state,
+        recording_id,
+        segment_index,
+        Disposition::Attachment,
+        &method,
+        &headers,
+    )
+    .await
+}
+
+pub async fn stream_segment(
+    State(app_state): State<Arc<AppState>>,
+    Path((recording_id, segment_index)): Path<(Uuid, i32)>,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<Response, RecordError> {
+    segment_file(
+        &app_state,
+        recording_id,
+        segment_index,
+        Disposition::Inline,
+        &method,
+        &headers,
+    )
+    .await
+}
+
+async fn recording_file(
+    app_state: &AppState,
+    recording_id: Uuid,
+    disposition: Disposition,
+    method: &Method,
+    headers: &HeaderMap,
+) -> Result<Response, RecordError> {
     let recording = app_state.database.get_recording(recording_id).await?;
+    if recording.is_segmented() {
+        return Err(RecordError::StreamError(format!(
+            "Recording {} is segmented; download its segments individually",
+            recording_id
+        )));
+    }
 
-    let file_path = PathBuf::from(&recording.file_path);
-    if !file_path.exists() {
+    file_response(
+        recording_id,
+        PathBuf::from(&recording.file_path),
+        &recording.file_name,
+        recording.container.content_type(),
+        disposition,
+        method,
+        headers,
+    )
+    .await
+}
+
+async fn segment_file(
+    app_state: &AppState,
+    recording_id: Uuid,
+    segment_index: i32,
+    disposition: Disposition,
+    method: &Method,
+    headers: &HeaderMap,
+) -> Result<Response, RecordError> {
+    let recording = app_state.database.get_recording(recording_id).await?;
+    let segment = app_state
+        .database
+        .get_segment(recording_id, segment_index)
+        .await?;
+
+    file_response(
+        recording_id,
+        PathBuf::from(&segment.file_path),
+        &segment.file_name,
+        recording.container.content_type(),
+        disposition,
+        method,
+        headers,
+    )
+    .await
+}
+
+/// 録画ファイルをレスポンスとして返す（Range・条件付きリクエスト・HEADに対応）
+async fn file_response(
+    recording_id: Uuid,
+    file_path: PathBuf,
+    file_name: &str,
+    content_type: &str,
+    disposition: Disposition,
+    method: &Method,
+    headers: &HeaderMap,
+) -> Result<Response, RecordError> {
+    if !file_path.is_file() {
         return Err(RecordError::RecordingNotFound(format!(
             "File not found for recording {}",
             recording_id
         )));
     }
 
-    let file = tokio::fs::File::open(&file_path).await?;
-    let stream = ReaderStream::new(file);
-    let body = Body::from_stream(stream);
-
-    let mut response = Response::new(body);
-    response
-        .headers_mut()
-        .insert(CONTENT_TYPE, "video/mp4".parse().unwrap());
-    response.headers_mut().insert(
-        CONTENT_DISPOSITION,
-        format!("attachment; filename=\"{}\"", recording.file_name)
-            .parse()
-            .unwrap(),
-    );
-
-    Ok(response)
+    serve_file(
+        &file_path,
+        file_name,
+        content_type,
+        disposition,
+        method,
+        headers,
+    )
+    .await
 }
 
 pub async fn delete(
@@ -227,7 +364,9 @@ pub async fn delete(
 
     // Delete file from filesystem
     let file_path = PathBuf::from(&recording.file_path);
-    if file_path.exists() {
+    if file_path.is_dir() {
+        tokio::fs::remove_dir_all(&file_path).await?;
+    } else if file_path.exists() {
         tokio::fs::remove_file(&file_path).await?;
     }
 
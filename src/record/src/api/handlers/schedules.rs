@@ -0,0 +1,67 @@
+use crate::app::AppState;
+use crate::error::RecordError;
+use crate::models::{RecordingSchedule, ScheduleListQuery, ScheduleRequest};
+use crate::scheduler::validate_schedule;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+pub async fn create(
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<ScheduleRequest>,
+) -> Result<(StatusCode, Json<RecordingSchedule>), RecordError> {
+    validate_schedule(&request)?;
+    // プロファイルの存在確認
+    app_state.database.get_profile(request.profile_id).await?;
+    let schedule = app_state.database.create_schedule(&request).await?;
+    info!(
+        "Created recording schedule: id={}, profile_id={}",
+        schedule.id, schedule.profile_id
+    );
+    Ok((StatusCode::CREATED, Json(schedule)))
+}
+
+pub async fn list(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ScheduleListQuery>,
+) -> Result<Json<Vec<RecordingSchedule>>, RecordError> {
+    let schedules = app_state.database.list_schedules(query.profile_id).await?;
+    Ok(Json(schedules))
+}
+
+pub async fn get(
+    State(app_state): State<Arc<AppState>>,
+    Path(schedule_id): Path<Uuid>,
+) -> Result<Json<RecordingSchedule>, RecordError> {
+    let schedule = app_state.database.get_schedule(schedule_id).await?;
+    Ok(Json(schedule))
+}
+
+pub async fn update(
+    State(app_state): State<Arc<AppState>>,
+    Path(schedule_id): Path<Uuid>,
+    Json(request): Json<ScheduleRequest>,
+) -> Result<Json<RecordingSchedule>, RecordError> {
+    validate_schedule(&request)?;
+    app_state.database.get_profile(request.profile_id).await?;
+    let schedule = app_state
+        .database
+        .update_schedule(schedule_id, &request)
+        .await?;
+    info!("Updated recording schedule: id={}", schedule.id);
+    Ok(Json(schedule))
+}
+
+pub async fn delete(
+    State(app_state): State<Arc<AppState>>,
+    Path(schedule_id): Path<Uuid>,
+) -> Result<StatusCode, RecordError> {
+    app_state.database.delete_schedule(schedule_id).await?;
+    info!("Deleted recording schedule: id={}", schedule_id);
+    Ok(StatusCode::NO_CONTENT)
+}
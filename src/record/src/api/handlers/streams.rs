@@ -1,40 +1,54 @@
 use crate::app::AppState;
 use crate::error::RecordError;
 use crate::models::{
-    ConnectRequest, ConnectResponse, DebugStatus, DisconnectResponse, StreamStatus,
+    ConnectRequest, ConnectResponse, DebugStatus, DisconnectResponse, StreamStatsResponse,
+    StreamStatus,
 };
-use crate::stream::StreamId;
+use crate::preroll::validate_preroll_seconds;
+use crate::stream::{sanitize_url, ConnectOptions, RecordingDefaults, StreamId};
 use axum::{
     extract::{Path, State},
     Json,
 };
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::info;
 use uuid::Uuid;
 
-#[derive(serde::Deserialize)]
-pub struct StartWebRTCQuery {
-    // pub signaling_url: String, // 未使用のためコメントアウト
-}
-
 pub async fn connect(
     State(app_state): State<Arc<AppState>>,
     Json(request): Json<ConnectRequest>,
 ) -> Result<Json<ConnectResponse>, RecordError> {
     info!(
         "Received stream connect request: protocol={}, url={}",
-        request.protocol, request.url
+        request.protocol,
+        sanitize_url(&request.url)
     );
 
     // Validate protocol
-    if request.protocol != "rtsp" && request.protocol != "webrtc" {
+    if request.protocol == "webrtc" {
+        return Err(RecordError::StreamError(
+            "WebRTC streams are published via WHIP: POST /api/v1/streams/{stream_id}/whip"
+                .to_string(),
+        ));
+    }
+    if request.protocol != "rtsp" {
         return Err(RecordError::StreamError(format!(
             "Unsupported protocol: {}",
             request.protocol
         )));
     }
 
+    if request.timeout_seconds == Some(0) {
+        return Err(RecordError::StreamError(
+            "timeout_seconds must be greater than 0".to_string(),
+        ));
+    }
+    if let Some(preroll_seconds) = request.preroll_seconds {
+        validate_preroll_seconds(preroll_seconds)?;
+    }
+
     // Generate stream ID
     let stream_id = Uuid::new_v4().to_string();
 
@@ -45,12 +59,26 @@ pub async fn connect(
             stream_id.clone(),
             request.protocol.clone(),
             request.url.clone(),
+            ConnectOptions {
+                name: request.name.clone(),
+                preroll_seconds: request
+                    .preroll_seconds
+                    .unwrap_or(app_state.config.stream.preroll_seconds),
+                username: request.username.clone(),
+                password: request.password.clone(),
+                latency_ms: request.latency_ms.unwrap_or(0),
+                transport: request.transport.unwrap_or_default(),
+                timeout: request.timeout_seconds.map(Duration::from_secs),
+                audio: request.audio.unwrap_or(app_state.config.stream.audio),
+                hls: request.hls.unwrap_or(app_state.config.hls.enabled),
+                recording_defaults: RecordingDefaults::default(),
+            },
         )
         .await?;
 
     info!(
         "Successfully initiated connection to stream: {}",
-        request.url
+        sanitize_url(&request.url)
     );
 
     Ok(Json(ConnectResponse {
@@ -118,3 +146,19 @@ pub async fn debug_status(
     );
     Ok(Json(detailed_status))
 }
+
+pub async fn stats(
+    State(app_state): State<Arc<AppState>>,
+    Path(stream_id): Path<StreamId>,
+) -> Result<Json<StreamStatsResponse>, RecordError> {
+    let stats = app_state
+        .stream_manager
+        .get_stats(&stream_id)
+        .await
+        .ok_or_else(|| RecordError::StreamError(format!("Stream {} not found", stream_id)))?;
+    Ok(Json(StreamStatsResponse {
+        stream_id,
+        current: stats.latest(),
+        history: stats.history(),
+    }))
+}
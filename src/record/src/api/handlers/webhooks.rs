@@ -0,0 +1,133 @@
+use crate::app::AppState;
+use crate::error::RecordError;
+use crate::models::{
+    Webhook, WebhookDelivery, WebhookDeliveryQuery, WebhookEventType, WebhookRequest,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+/// 配信履歴の既定の件数と上限
+const DEFAULT_DELIVERY_LIMIT: i64 = 100;
+const MAX_DELIVERY_LIMIT: i64 = 1000;
+
+pub async fn create(
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<WebhookRequest>,
+) -> Result<(StatusCode, Json<Webhook>), RecordError> {
+    validate(&request)?;
+    let secret_encrypted = encrypt_secret(&app_state, &request)?;
+    let webhook = app_state
+        .database
+        .create_webhook(&request, secret_encrypted.as_deref())
+        .await?;
+    info!("Created webhook: id={}, name={}", webhook.id, webhook.name);
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+pub async fn list(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Webhook>>, RecordError> {
+    let webhooks = app_state.database.list_webhooks().await?;
+    Ok(Json(webhooks))
+}
+
+pub async fn get(
+    State(app_state): State<Arc<AppState>>,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<Json<Webhook>, RecordError> {
+    let webhook = app_state.database.get_webhook(webhook_id).await?;
+    Ok(Json(webhook))
+}
+
+pub async fn update(
+    State(app_state): State<Arc<AppState>>,
+    Path(webhook_id): Path<Uuid>,
+    Json(request): Json<WebhookRequest>,
+) -> Result<Json<Webhook>, RecordError> {
+    validate(&request)?;
+    let secret_encrypted = encrypt_secret(&app_state, &request)?;
+    let webhook = app_state
+        .database
+        .update_webhook(webhook_id, &request, secret_encrypted.as_deref())
+        .await?;
+    info!("Updated webhook: id={}", webhook.id);
+    Ok(Json(webhook))
+}
+
+pub async fn delete(
+    State(app_state): State<Arc<AppState>>,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<StatusCode, RecordError> {
+    app_state.database.delete_webhook(webhook_id).await?;
+    info!("Deleted webhook: id={}", webhook_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 配信履歴（新しい順）
+pub async fn list_deliveries(
+    State(app_state): State<Arc<AppState>>,
+    Path(webhook_id): Path<Uuid>,
+    Query(query): Query<WebhookDeliveryQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>, RecordError> {
+    // Webhookの存在確認
+    app_state.database.get_webhook(webhook_id).await?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_DELIVERY_LIMIT)
+        .clamp(1, MAX_DELIVERY_LIMIT);
+    let deliveries = app_state
+        .database
+        .list_webhook_deliveries(webhook_id, query.status, limit)
+        .await?;
+    Ok(Json(deliveries))
+}
+
+/// 接続確認用のpingを送信する（送信結果は配信履歴で確認する）
+pub async fn ping(
+    State(app_state): State<Arc<AppState>>,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<WebhookDelivery>), RecordError> {
+    let webhook = app_state.database.get_webhook(webhook_id).await?;
+    let delivery = app_state.webhooks.ping(&webhook).await?;
+    Ok((StatusCode::ACCEPTED, Json(delivery)))
+}
+
+/// リクエストのシークレットを暗号化する（空文字列はシークレットの削除としてそのまま渡す）
+fn encrypt_secret(
+    app_state: &AppState,
+    request: &WebhookRequest,
+) -> Result<Option<String>, RecordError> {
+    match &request.secret {
+        Some(secret) if !secret.is_empty() => app_state.credentials.encrypt(secret).map(Some),
+        Some(_) => Ok(Some(String::new())),
+        None => Ok(None),
+    }
+}
+
+fn validate(request: &WebhookRequest) -> Result<(), RecordError> {
+    let url = reqwest::Url::parse(&request.url)
+        .map_err(|e| RecordError::StreamError(format!("Invalid webhook url: {}", e)))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(RecordError::StreamError(format!(
+            "Unsupported webhook url scheme: {}",
+            url.scheme()
+        )));
+    }
+    if request.event_types.is_empty() {
+        return Err(RecordError::StreamError(
+            "event_types must not be empty".to_string(),
+        ));
+    }
+    if request.event_types.contains(&WebhookEventType::Ping) {
+        return Err(RecordError::StreamError(
+            "ping cannot be subscribed; use the ping endpoint instead".to_string(),
+        ));
+    }
+    Ok(())
+}
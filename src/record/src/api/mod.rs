@@ -1,9 +1,13 @@
+mod files;
 mod handlers;
 
 use crate::app::AppState;
 use crate::error::RecordError;
 use axum::{
-    routing::{delete, get, post},
+    extract::{MatchedPath, Request, State},
+    middleware::{self, Next},
+    response::Response,
+    routing::{delete, get, patch, post},
     Router,
 };
 use std::sync::Arc;
@@ -40,6 +44,9 @@ pub async fn serve(app_state: Arc<AppState>) -> Result<(), RecordError> {
 fn create_router(app_state: Arc<AppState>) -> Router {
     Router::new()
         .route("/health", get(handlers::health))
+        .route("/metrics", get(handlers::metrics::metrics))
+        .route("/api/v1/events", get(handlers::events::sse))
+        .route("/api/v1/events/ws", get(handlers::events::websocket))
         .route("/api/v1/streams/connect", post(handlers::streams::connect))
         .route(
             "/api/v1/streams/status",
@@ -53,6 +60,10 @@ fn create_router(app_state: Arc<AppState>) -> Router {
             "/api/v1/streams/:stream_id/debug",
             get(handlers::streams::debug_status),
         )
+        .route(
+            "/api/v1/streams/:stream_id/stats",
+            get(handlers::streams::stats),
+        )
         .route(
             "/api/v1/streams/:stream_id/disconnect",
             post(handlers::streams::disconnect),
@@ -61,14 +72,46 @@ fn create_router(app_state: Arc<AppState>) -> Router {
             "/api/v1/streams/:stream_id/webrtc",
             post(handlers::webrtcs::webrtc_signaling),
         )
+        .route(
+            "/api/v1/streams/:stream_id/whep",
+            post(handlers::webrtcs::whep_offer),
+        )
+        .route(
+            "/api/v1/streams/:stream_id/whep/:session_id",
+            patch(handlers::webrtcs::whep_patch).delete(handlers::webrtcs::whep_delete),
+        )
+        .route(
+            "/api/v1/streams/:stream_id/whip",
+            post(handlers::webrtcs::whip_offer),
+        )
+        .route(
+            "/api/v1/streams/:stream_id/whip/:session_id",
+            patch(handlers::webrtcs::whip_patch).delete(handlers::webrtcs::whip_delete),
+        )
+        .route(
+            "/api/v1/streams/:stream_id/recordings/stop",
+            post(handlers::recordings::stop_stream),
+        )
+        .route(
+            "/api/v1/streams/:stream_id/hls/:file_name",
+            get(handlers::hls::file),
+        )
         .route(
             "/api/v1/recordings/:stream_id/start",
             post(handlers::recordings::start),
         )
         .route(
-            "/api/v1/recordings/:stream_id/stop",
+            "/api/v1/recordings/:recording_id/stop",
             post(handlers::recordings::stop),
         )
+        .route(
+            "/api/v1/recordings/:recording_id/pause",
+            post(handlers::recordings::pause),
+        )
+        .route(
+            "/api/v1/recordings/:recording_id/resume",
+            post(handlers::recordings::resume),
+        )
         .route("/api/v1/recordings", get(handlers::recordings::list))
         .route(
             "/api/v1/recordings/:recording_id",
@@ -78,10 +121,77 @@ fn create_router(app_state: Arc<AppState>) -> Router {
             "/api/v1/recordings/:recording_id/download",
             get(handlers::recordings::download),
         )
+        .route(
+            "/api/v1/recordings/:recording_id/stream",
+            get(handlers::recordings::stream),
+        )
         .route(
             "/api/v1/recordings/:recording_id",
             delete(handlers::recordings::delete),
         )
+        .route(
+            "/api/v1/recordings/:recording_id/segments",
+            get(handlers::recordings::list_segments),
+        )
+        .route(
+            "/api/v1/recordings/:recording_id/segments/:segment_index/download",
+            get(handlers::recordings::download_segment),
+        )
+        .route(
+            "/api/v1/recordings/:recording_id/segments/:segment_index/stream",
+            get(handlers::recordings::stream_segment),
+        )
+        .route(
+            "/api/v1/recordings/:recording_id/clips",
+            get(handlers::clips::list).post(handlers::clips::create),
+        )
+        .route("/api/v1/clip-exports/:export_id", get(handlers::clips::get))
+        .route(
+            "/api/v1/profiles",
+            get(handlers::profiles::list).post(handlers::profiles::create),
+        )
+        .route(
+            "/api/v1/profiles/:profile_id",
+            get(handlers::profiles::get)
+                .put(handlers::profiles::update)
+                .delete(handlers::profiles::delete),
+        )
+        .route(
+            "/api/v1/profiles/:profile_id/connect",
+            post(handlers::profiles::connect),
+        )
+        .route(
+            "/api/v1/schedules",
+            get(handlers::schedules::list).post(handlers::schedules::create),
+        )
+        .route(
+            "/api/v1/schedules/:schedule_id",
+            get(handlers::schedules::get)
+                .put(handlers::schedules::update)
+                .delete(handlers::schedules::delete),
+        )
+        .route(
+            "/api/v1/webhooks",
+            get(handlers::webhooks::list).post(handlers::webhooks::create),
+        )
+        .route(
+            "/api/v1/webhooks/:webhook_id",
+            get(handlers::webhooks::get)
+                .put(handlers::webhooks::update)
+                .delete(handlers::webhooks::delete),
+        )
+        .route(
+            "/api/v1/webhooks/:webhook_id/deliveries",
+            get(handlers::webhooks::list_deliveries),
+        )
+        .route(
+            "/api/v1/webhooks/:webhook_id/ping",
+            post(handlers::webhooks::ping),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            record_http_metrics,
+        ))
         .layer(
             ServiceBuilder::new()
                 .layer(
@@ -93,3 +203,25 @@ fn create_router(app_state: Arc<AppState>) -> Router {
         )
         .with_state(app_state)
 }
+
+/// ルートごとのHTTPリクエストの処理時間をメトリクスに記録する
+async fn record_http_metrics(
+    State(app_state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let started = std::time::Instant::now();
+    let response = next.run(request).await;
+    app_state
+        .metrics
+        .http_request_duration
+        .with_label_values(&[&method, &route, response.status().as_str()])
+        .observe(started.elapsed().as_secs_f64());
+    response
+}
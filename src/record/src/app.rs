@@ -1,19 +1,415 @@
+use crate::clips::ClipExporter;
 use crate::config::Config;
-use crate::database::Database;
-use crate::stream::StreamManager;
+use crate::credentials::CredentialCipher;
+use crate::database::{to_bigint, Database};
+use crate::error::RecordError;
+use crate::events::{EventBus, EventKind};
+use crate::metrics::Metrics;
+use crate::models::{
+    NewRecording, RecordingContainer, StartRecordingRequest, StartRecordingResponse, StreamProfile,
+};
+use crate::recording::{
+    render_file_stem, FileNameContext, RecordingLimits, RecordingOptions, SegmentOptions,
+};
+use crate::stream::{sanitize_url, ConnectOptions, RecordingDefaults, StreamId, StreamManager};
+use crate::webhooks::WebhookDispatcher;
+use chrono::{Local, Utc};
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
 
 pub struct AppState {
     pub config: Config,
     pub database: Database,
     pub stream_manager: StreamManager,
+    pub credentials: CredentialCipher,
+    pub metrics: Metrics,
+    pub events: EventBus,
+    pub webhooks: Arc<WebhookDispatcher>,
+    pub clips: Arc<ClipExporter>,
 }
 
 impl AppState {
-    pub fn new(config: Config, database: Database) -> Self {
-        Self {
+    pub fn new(config: Config, database: Database) -> Result<Self, RecordError> {
+        let credentials = CredentialCipher::new(
+            config
+                .security
+                .credentials_key
+                .as_ref()
+                .map(|key| key.expose()),
+        )?;
+        let metrics = Metrics::new()?;
+        let events = EventBus::new();
+        let webhooks = Arc::new(WebhookDispatcher::new(
+            config.webhooks.clone(),
+            database.clone(),
+            credentials.clone(),
+        )?);
+        let clips = Arc::new(ClipExporter::new(config.clips.clone(), database.clone()));
+        Ok(Self {
             config: config.clone(),
+            stream_manager: StreamManager::new(
+                config,
+                database.clone(),
+                metrics.clone(),
+                events.clone(),
+            ),
             database,
-            stream_manager: StreamManager::new(config),
+            credentials,
+            metrics,
+            events,
+            webhooks,
+            clips,
+        })
+    }
+
+    /// 録画ファイルのパスを決定してDBに登録し、ストリームの録画を開始する
+    pub async fn start_recording(
+        self: &Arc<Self>,
+        stream_id: &StreamId,
+        request: StartRecordingRequest,
+    ) -> Result<StartRecordingResponse, RecordError> {
+        let recording_id = Uuid::new_v4().to_string();
+        info!("[recording {}] Generated recording ID", recording_id);
+
+        let stream = self
+            .stream_manager
+            .get_status(stream_id)
+            .await
+            .ok_or_else(|| RecordError::StreamError(format!("Stream {} not found", stream_id)))?;
+        let request = stream.recording_defaults.apply(request);
+        let segment = segment_options(&request, self)?;
+        let limits = recording_limits(&request)?;
+        let container = request.container.unwrap_or(self.config.recording.container);
+        let start_time = Utc::now();
+        let stem = render_file_stem(
+            &self.config.recording.filename_template,
+            &FileNameContext {
+                stream_id,
+                stream_name: stream.name.as_deref(),
+                recording_id: &recording_id,
+                label: request.filename.as_deref(),
+                start_time: start_time.with_timezone(&Local),
+            },
+        )?;
+        let mut path = recording_path(self, &stem, segment.is_some(), container);
+        if path.exists() {
+            // テンプレートにrecording_idが含まれず既存ファイルと衝突する場合は末尾に付与する
+            let mut unique_stem = stem.into_os_string();
+            unique_stem.push(format!("_{}", recording_id));
+            path = recording_path(
+                self,
+                &PathBuf::from(unique_stem),
+                segment.is_some(),
+                container,
+            );
+        }
+        // セグメント録画の場合はセグメントを格納するディレクトリ、それ以外は親ディレクトリを作成する
+        let directory = if segment.is_some() {
+            Some(path.as_path())
+        } else {
+            path.parent()
+        };
+        if let Some(directory) = directory {
+            tokio::fs::create_dir_all(directory).await?;
+        }
+        let location = path.to_string_lossy().to_string();
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| recording_id.clone());
+        info!(
+            "[recording {}] Recording file location: {}",
+            recording_id, location
+        );
+
+        // 映像が届かないストリームで失敗する録画をDBに残さないよう、登録前に最初のキーフレームを待つ
+        self.stream_manager.wait_until_ready(stream_id).await?;
+
+        // DBに録画情報を登録
+        self.database
+            .create_recording(&NewRecording {
+                id: Uuid::parse_str(&recording_id).unwrap(),
+                file_name,
+                file_path: location.clone(),
+                start_time,
+                segment_duration_seconds: to_bigint(
+                    segment.and_then(|s| s.max_duration_seconds),
+                    "segment_duration_seconds",
+                )?,
+                segment_max_size_bytes: to_bigint(
+                    segment.and_then(|s| s.max_size_bytes),
+                    "segment_max_size_bytes",
+                )?,
+                container,
+                max_duration_seconds: to_bigint(
+                    request.max_duration_seconds,
+                    "max_duration_seconds",
+                )?,
+                max_size_bytes: to_bigint(request.max_size_bytes, "max_size_bytes")?,
+                stop_at: request.stop_at,
+                stream_id: stream_id.clone(),
+                protocol: stream.protocol.clone(),
+                source_url: stream.url.as_deref().map(sanitize_url),
+            })
+            .await?;
+
+        let recording_id2 = recording_id.clone();
+        let options = RecordingOptions {
+            location: location.clone(),
+            segment,
+            container,
+            preroll: request.preroll.unwrap_or(true),
+            limits,
+        };
+        let app_state = self.clone();
+        let stream_id2 = stream_id.clone();
+        let result = tokio::task::spawn_blocking(AssertUnwindSafe(move || {
+            futures::executor::block_on(app_state.stream_manager.start_recording(
+                &stream_id2,
+                &recording_id2,
+                &options,
+            ))
+        }))
+        .await;
+
+        match result {
+            Ok(Ok(started)) => {
+                info!(
+                    "[recording {}] Successfully started recording for stream: {} ({:?})",
+                    recording_id, stream_id, started.media
+                );
+                let preroll_ms = started.preroll.mseconds() as i64;
+                self.database
+                    .update_recording_started(
+                        Uuid::parse_str(&recording_id).unwrap(),
+                        preroll_ms,
+                        &started.media,
+                    )
+                    .await?;
+                self.events.publish(
+                    Some(stream_id),
+                    EventKind::RecordingStarted {
+                        recording_id: recording_id.clone(),
+                    },
+                );
+                Ok(StartRecordingResponse {
+                    recording_id,
+                    stream_id: stream_id.clone(),
+                    location,
+                    message: format!("Recording started successfully for stream: {}", stream_id),
+                    status: "RECORDING".to_string(),
+                    preroll_ms,
+                })
+            }
+            Ok(Err(e)) => {
+                error!(
+                    "[recording {}] Failed to start recording for stream {}: {}",
+                    recording_id, stream_id, e
+                );
+                mark_failed(self, &recording_id).await;
+                self.events.publish(
+                    Some(stream_id),
+                    EventKind::RecordingFailed {
+                        recording_id,
+                        message: e.to_string(),
+                    },
+                );
+                Err(e)
+            }
+            Err(e) => {
+                error!(
+                    "[recording {}] Panic occurred in start_recording for stream {}: {:?}",
+                    recording_id, stream_id, e
+                );
+                mark_failed(self, &recording_id).await;
+                let e =
+                    RecordError::StreamError(format!("Panic occurred in start_recording: {:?}", e));
+                self.events.publish(
+                    Some(stream_id),
+                    EventKind::RecordingFailed {
+                        recording_id,
+                        message: e.to_string(),
+                    },
+                );
+                Err(e)
+            }
         }
     }
+
+    /// プロファイルのストリームに接続済みでなければ接続する。stream_idはプロファイルIDと同じ
+    pub async fn connect_profile(
+        self: &Arc<Self>,
+        profile: &StreamProfile,
+    ) -> Result<StreamId, RecordError> {
+        let stream_id = profile.id.to_string();
+        if self.stream_manager.get_status(&stream_id).await.is_some() {
+            return Ok(stream_id);
+        }
+
+        info!(
+            "[stream {}] Connecting stream profile '{}'",
+            stream_id, profile.name
+        );
+        let app_state = self.clone();
+        let stream_id2 = stream_id.clone();
+        let protocol = profile.protocol.clone();
+        let url = profile.url.clone();
+        let options = profile_connect_options(profile, self)?;
+        tokio::task::spawn_blocking(AssertUnwindSafe(move || {
+            futures::executor::block_on(
+                app_state
+                    .stream_manager
+                    .connect(stream_id2, protocol, url, options),
+            )
+        }))
+        .await
+        .map_err(|e| RecordError::InternalError(format!("Connect task failed: {:?}", e)))??;
+        Ok(stream_id)
+    }
+
+    /// auto_connectが有効な全プロファイルに接続する。接続に失敗したプロファイルはログに残して続行する
+    pub async fn connect_auto_connect_profiles(self: &Arc<Self>) -> Result<(), RecordError> {
+        for profile in self.database.list_auto_connect_profiles().await? {
+            match self.connect_profile(&profile).await {
+                Ok(stream_id) => info!(
+                    "[stream {}] Auto-connected stream profile '{}'",
+                    stream_id, profile.name
+                ),
+                Err(e) => error!(
+                    "[stream {}] Failed to auto-connect stream profile '{}': {}",
+                    profile.id, profile.name, e
+                ),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// プロファイルの接続設定と録画の既定値から接続オプションを作成する
+fn profile_connect_options(
+    profile: &StreamProfile,
+    app_state: &AppState,
+) -> Result<ConnectOptions, RecordError> {
+    let password = profile
+        .password_encrypted
+        .as_ref()
+        .map(|stored| app_state.credentials.decrypt(stored.expose()))
+        .transpose()?;
+    Ok(ConnectOptions {
+        name: Some(profile.name.clone()),
+        preroll_seconds: profile
+            .preroll_seconds
+            .map(|v| v as u64)
+            .unwrap_or(app_state.config.stream.preroll_seconds),
+        username: profile.username.clone(),
+        password,
+        latency_ms: profile.latency_ms as u32,
+        transport: profile.transport,
+        timeout: profile
+            .timeout_seconds
+            .map(|v| std::time::Duration::from_secs(v as u64)),
+        audio: profile.audio.unwrap_or(app_state.config.stream.audio),
+        hls: app_state.config.hls.enabled,
+        recording_defaults: RecordingDefaults {
+            container: profile.container,
+            segment_duration_seconds: profile.segment_duration_seconds.map(|v| v as u64),
+            segment_max_size_bytes: profile.segment_max_size_bytes.map(|v| v as u64),
+        },
+    })
+}
+
+/// リクエストと設定からセグメント録画の分割条件を決定する
+fn segment_options(
+    request: &StartRecordingRequest,
+    app_state: &AppState,
+) -> Result<Option<SegmentOptions>, RecordError> {
+    let max_duration_seconds = request
+        .segment_duration_seconds
+        .or(app_state.config.recording.segment_duration_seconds);
+    let max_size_bytes = request.segment_max_size_bytes;
+    // splitmuxsinkは0を無制限として扱い分割しないため、0は受け付けない
+    if max_duration_seconds == Some(0) {
+        return Err(RecordError::StreamError(
+            "segment_duration_seconds must be greater than 0".to_string(),
+        ));
+    }
+    if max_size_bytes == Some(0) {
+        return Err(RecordError::StreamError(
+            "segment_max_size_bytes must be greater than 0".to_string(),
+        ));
+    }
+    if max_duration_seconds.is_none() && max_size_bytes.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(SegmentOptions {
+        max_duration_seconds,
+        max_size_bytes,
+    }))
+}
+
+/// リクエストから録画の自動停止条件を決定する
+fn recording_limits(request: &StartRecordingRequest) -> Result<RecordingLimits, RecordError> {
+    if request.max_duration_seconds == Some(0) {
+        return Err(RecordError::StreamError(
+            "max_duration_seconds must be greater than 0".to_string(),
+        ));
+    }
+    if request.max_size_bytes == Some(0) {
+        return Err(RecordError::StreamError(
+            "max_size_bytes must be greater than 0".to_string(),
+        ));
+    }
+    if let Some(stop_at) = request.stop_at {
+        if stop_at <= Utc::now() {
+            return Err(RecordError::StreamError(format!(
+                "stop_at must be in the future: {}",
+                stop_at
+            )));
+        }
+    }
+    Ok(RecordingLimits {
+        max_duration: request
+            .max_duration_seconds
+            .map(std::time::Duration::from_secs),
+        max_size_bytes: request.max_size_bytes,
+        stop_at: request.stop_at,
+    })
+}
+
+/// 録画開始に失敗した録画をFAILEDにする
+async fn mark_failed(app_state: &AppState, recording_id: &str) {
+    let Ok(id) = Uuid::parse_str(recording_id) else {
+        return;
+    };
+    if let Err(e) = app_state
+        .database
+        .update_recording_failed(id, Some(Utc::now()), None, None, None)
+        .await
+    {
+        error!(
+            "[recording {}] Failed to mark recording as failed: {}",
+            recording_id, e
+        );
+    }
+}
+
+/// recording_directory配下の録画パスを返す（セグメント録画の場合はディレクトリ）
+fn recording_path(
+    app_state: &AppState,
+    stem: &std::path::Path,
+    segmented: bool,
+    container: RecordingContainer,
+) -> PathBuf {
+    let mut path = app_state
+        .config
+        .recording_directory
+        .join(stem)
+        .into_os_string();
+    if !segmented {
+        path.push(format!(".{}", container.extension()));
+    }
+    PathBuf::from(path)
 }
@@ -0,0 +1,489 @@
+use crate::config::ClipConfig;
+use crate::database::Database;
+use crate::error::RecordError;
+use crate::models::{
+    ClipExport, ClipExportMode, ClipExportRequest, ClipExportStatus, Recording, RecordingContainer,
+    RecordingStatus,
+};
+use crate::reconcile::probe_duration;
+use crate::recording::{build_muxer, file_size};
+use chrono::Utc;
+use gstreamer::prelude::*;
+use gstreamer::{
+    ClockTime, Element, ElementFactory, MessageView, Pad, PadProbeId, PadProbeType, Pipeline,
+    SeekFlags, SeekType, State,
+};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// 進捗を確認する間隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+/// 進捗をDBに書き込む最小の変化量
+const PROGRESS_STEP: f64 = 0.01;
+
+/// シーク前のバッファを止めるプローブ（シーク後はNone）
+type BlockingProbes = Arc<Mutex<Option<Vec<(Pad, PadProbeId)>>>>;
+
+/// 録画の一部を新しい録画として書き出すジョブを実行する
+pub struct ClipExporter {
+    config: ClipConfig,
+    database: Database,
+    jobs: Arc<Semaphore>,
+}
+
+/// 書き出しのパイプライン。demuxしたストリームはシークするまでブロックしておく
+struct ClipPipeline {
+    pipeline: Pipeline,
+    /// 全てのストリームのパッドが揃い、シークできる状態になったか
+    ready: Arc<AtomicBool>,
+    blocked: BlockingProbes,
+}
+
+impl ClipExporter {
+    pub fn new(config: ClipConfig, database: Database) -> Self {
+        let jobs = Arc::new(Semaphore::new(config.max_concurrent_jobs));
+        Self {
+            config,
+            database,
+            jobs,
+        }
+    }
+
+    /// 書き出しのジョブを登録し、バックグラウンドで実行する
+    pub async fn create(
+        self: &Arc<Self>,
+        recording_id: Uuid,
+        request: &ClipExportRequest,
+    ) -> Result<ClipExport, RecordError> {
+        let recording = self.database.get_recording(recording_id).await?;
+        let (start_offset_ms, end_offset_ms) = validate_request(&recording, request)?;
+        if !Path::new(&recording.file_path).is_file() {
+            return Err(RecordError::NotFound(format!(
+                "Recording file not found: {}",
+                recording.file_path
+            )));
+        }
+        let export = self
+            .database
+            .create_clip_export(recording_id, start_offset_ms, end_offset_ms, request.mode)
+            .await?;
+        info!(
+            "[clip {}] Queued clip export from recording {} ({}ms-{}ms, {:?})",
+            export.id, recording_id, start_offset_ms, end_offset_ms, request.mode
+        );
+
+        let exporter = self.clone();
+        let job = export.clone();
+        tokio::spawn(async move { exporter.run(job, recording).await });
+        Ok(export)
+    }
+
+    /// 前回の異常終了で完了しなかったジョブを失敗にする
+    pub async fn fail_interrupted(&self) -> Result<(), RecordError> {
+        let count = self.database.fail_interrupted_clip_exports().await?;
+        if count > 0 {
+            warn!("Marked {} interrupted clip exports as failed", count);
+        }
+        Ok(())
+    }
+
+    async fn run(self: Arc<Self>, export: ClipExport, parent: Recording) {
+        let Ok(_permit) = self.jobs.clone().acquire_owned().await else {
+            return;
+        };
+        let clip_path = clip_path(&parent, export.id);
+        let mut progress = 0.0;
+        match self
+            .export(&export, &parent, &clip_path, &mut progress)
+            .await
+        {
+            Ok(clip) => {
+                info!(
+                    "[clip {}] Exported clip recording {} ({}s, {} bytes)",
+                    export.id,
+                    clip.id,
+                    clip.duration_seconds.unwrap_or_default(),
+                    clip.file_size_bytes.unwrap_or_default()
+                );
+                if let Err(e) = self
+                    .database
+                    .update_clip_export(
+                        export.id,
+                        ClipExportStatus::Completed,
+                        1.0,
+                        None,
+                        Some(clip.id),
+                    )
+                    .await
+                {
+                    error!(
+                        "[clip {}] Failed to mark clip export as completed: {}",
+                        export.id, e
+                    );
+                }
+            }
+            Err(e) => {
+                error!("[clip {}] Failed to export clip: {}", export.id, e);
+                let _ = tokio::fs::remove_file(&clip_path).await;
+                if let Err(e) = self
+                    .database
+                    .update_clip_export(
+                        export.id,
+                        ClipExportStatus::Failed,
+                        progress,
+                        Some(&e.to_string()),
+                        None,
+                    )
+                    .await
+                {
+                    error!(
+                        "[clip {}] Failed to mark clip export as failed: {}",
+                        export.id, e
+                    );
+                }
+            }
+        }
+    }
+
+    /// クリップのファイルを書き出し、録画として登録する
+    async fn export(
+        &self,
+        export: &ClipExport,
+        parent: &Recording,
+        clip_path: &Path,
+        progress: &mut f64,
+    ) -> Result<Recording, RecordError> {
+        self.database
+            .update_clip_export(export.id, ClipExportStatus::Running, 0.0, None, None)
+            .await?;
+        let container = clip_container(parent.container);
+        let clip = build_pipeline(
+            Path::new(&parent.file_path),
+            clip_path,
+            container,
+            export.mode,
+        )?;
+        let result = self.run_pipeline(&clip, export, progress).await;
+        let _ = clip.pipeline.set_state(State::Null);
+        result?;
+
+        let path = clip_path.to_path_buf();
+        let (duration_seconds, size) =
+            tokio::task::spawn_blocking(move || (probe_duration(&path), file_size(&path)))
+                .await
+                .map_err(|e| RecordError::InternalError(format!("Probe task failed: {:?}", e)))?;
+        let duration_seconds = duration_seconds
+            .ok_or_else(|| RecordError::StreamError("Exported clip is not playable".to_string()))?;
+
+        // 録画ファイルの先頭は録画開始時刻からプリロール分だけ遡った時刻
+        let start_time = parent.start_time
+            + chrono::Duration::milliseconds(export.start_offset_ms - parent.preroll_ms);
+        let (codec, audio_codec) = match export.mode {
+            ClipExportMode::Copy => (parent.codec.clone(), parent.audio_codec.clone()),
+            ClipExportMode::Reencode => (
+                Some("h264".to_string()),
+                parent.audio_codec.as_ref().map(|_| "aac".to_string()),
+            ),
+        };
+        let now = Utc::now();
+        let clip = Recording {
+            id: Uuid::new_v4(),
+            file_name: clip_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            file_path: clip_path.to_string_lossy().to_string(),
+            start_time,
+            end_time: Some(start_time + chrono::Duration::seconds(duration_seconds)),
+            duration_seconds: Some(duration_seconds),
+            file_size_bytes: Some(size),
+            status: RecordingStatus::Completed,
+            created_at: now,
+            updated_at: now,
+            segment_duration_seconds: None,
+            segment_max_size_bytes: None,
+            container,
+            preroll_ms: 0,
+            max_duration_seconds: None,
+            max_size_bytes: None,
+            stop_at: None,
+            stop_reason: None,
+            stream_id: parent.stream_id.clone(),
+            protocol: parent.protocol.clone(),
+            source_url: parent.source_url.clone(),
+            codec,
+            width: parent.width,
+            height: parent.height,
+            framerate: parent.framerate,
+            audio_codec,
+            parent_recording_id: Some(parent.id),
+        };
+        self.database.create_clip_recording(&clip).await
+    }
+
+    /// ストリームが揃ったら開始位置へシークし、EOS（終了位置）まで進捗を記録する
+    async fn run_pipeline(
+        &self,
+        clip: &ClipPipeline,
+        export: &ClipExport,
+        progress: &mut f64,
+    ) -> Result<(), RecordError> {
+        let bus = clip
+            .pipeline
+            .bus()
+            .ok_or_else(|| RecordError::InternalError("Failed to get bus from pipeline".into()))?;
+        let start = ClockTime::from_mseconds(export.start_offset_ms as u64);
+        let end = ClockTime::from_mseconds(export.end_offset_ms as u64);
+        let deadline = Instant::now() + Duration::from_secs(self.config.timeout_seconds);
+        let mut seeked = false;
+        let mut reported = 0.0;
+
+        clip.pipeline.set_state(State::Playing)?;
+        loop {
+            while let Some(msg) =
+                bus.pop_filtered(&[gstreamer::MessageType::Eos, gstreamer::MessageType::Error])
+            {
+                match msg.view() {
+                    MessageView::Eos(..) => return Ok(()),
+                    MessageView::Error(err) => {
+                        return Err(RecordError::StreamError(format!(
+                            "{} ({:?})",
+                            err.error(),
+                            err.debug()
+                        )))
+                    }
+                    _ => {}
+                }
+            }
+
+            if !seeked && clip.ready.load(Ordering::SeqCst) {
+                // 通常はキーフレームの位置から書き出し、再エンコードの場合はフレーム単位で切り出す
+                let flags = match export.mode {
+                    ClipExportMode::Copy => {
+                        SeekFlags::FLUSH | SeekFlags::KEY_UNIT | SeekFlags::SNAP_BEFORE
+                    }
+                    ClipExportMode::Reencode => SeekFlags::FLUSH | SeekFlags::ACCURATE,
+                };
+                clip.pipeline
+                    .seek(1.0, flags, SeekType::Set, start, SeekType::Set, end)
+                    .map_err(|_| {
+                        RecordError::StreamError("Failed to seek recording file".to_string())
+                    })?;
+                clip.unblock();
+                seeked = true;
+            } else if seeked {
+                if let Some(position) = clip.pipeline.query_position::<ClockTime>() {
+                    *progress = (position.saturating_sub(start).nseconds() as f64
+                        / end.saturating_sub(start).nseconds().max(1) as f64)
+                        .clamp(0.0, 1.0);
+                }
+                if *progress - reported >= PROGRESS_STEP {
+                    reported = *progress;
+                    self.database
+                        .update_clip_export_progress(export.id, reported)
+                        .await?;
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(RecordError::StreamError(
+                    "Timeout while exporting clip".to_string(),
+                ));
+            }
+            tokio::time::sleep(PROGRESS_INTERVAL).await;
+        }
+    }
+}
+
+impl ClipPipeline {
+    /// シーク後にプローブを外し、シーク先からのバッファを流す
+    fn unblock(&self) {
+        let Some(probes) = self.blocked.lock().unwrap().take() else {
+            return;
+        };
+        for (pad, probe_id) in probes {
+            pad.remove_probe(probe_id);
+        }
+    }
+}
+
+/// filesrc → parsebin（再エンコードの場合はdecodebin）→ muxer → filesink のパイプラインを作成する
+fn build_pipeline(
+    source: &Path,
+    destination: &Path,
+    container: RecordingContainer,
+    mode: ClipExportMode,
+) -> Result<ClipPipeline, RecordError> {
+    let pipeline = Pipeline::new();
+    let filesrc = ElementFactory::make("filesrc")
+        .property("location", source.to_string_lossy().to_string())
+        .build()?;
+    let demux = match mode {
+        ClipExportMode::Copy => ElementFactory::make("parsebin").build()?,
+        ClipExportMode::Reencode => ElementFactory::make("decodebin").build()?,
+    };
+    let muxer = build_muxer(container)?;
+    let filesink = ElementFactory::make("filesink")
+        .property("location", destination.to_string_lossy().to_string())
+        .property("sync", false)
+        .build()?;
+    pipeline.add_many([&filesrc, &demux, &muxer, &filesink])?;
+    filesrc.link(&demux)?;
+    muxer.link(&filesink)?;
+
+    let ready = Arc::new(AtomicBool::new(false));
+    let blocked: BlockingProbes = Arc::new(Mutex::new(Some(Vec::new())));
+    let pipeline_weak = pipeline.downgrade();
+    let muxer_weak = muxer.downgrade();
+    let blocked2 = blocked.clone();
+    demux.connect_pad_added(move |_, src_pad| {
+        let (Some(pipeline), Some(muxer)) = (pipeline_weak.upgrade(), muxer_weak.upgrade()) else {
+            return;
+        };
+        // シーク前のバッファがmuxerに届かないよう、シークするまでブロックする
+        if let Some(probes) = blocked2.lock().unwrap().as_mut() {
+            if let Some(probe_id) = src_pad.add_probe(
+                PadProbeType::BLOCK | PadProbeType::BUFFER | PadProbeType::BUFFER_LIST,
+                |_, _| gstreamer::PadProbeReturn::Ok,
+            ) {
+                probes.push((src_pad.clone(), probe_id));
+            }
+        }
+        if let Err(e) = link_stream(&pipeline, &muxer, src_pad, mode) {
+            warn!("Failed to link clip stream {}: {}", src_pad.name(), e);
+        }
+    });
+    let ready2 = ready.clone();
+    demux.connect_no_more_pads(move |_| ready2.store(true, Ordering::SeqCst));
+
+    Ok(ClipPipeline {
+        pipeline,
+        ready,
+        blocked,
+    })
+}
+
+/// demuxしたストリームをmuxerにつなぐ。映像・音声以外のストリームは破棄する
+pub fn link_stream(
+    pipeline: &Pipeline,
+    muxer: &Element,
+    src_pad: &Pad,
+    mode: ClipExportMode,
+) -> Result<(), RecordError> {
+    let caps = src_pad
+        .current_caps()
+        .unwrap_or_else(|| src_pad.query_caps(None));
+    let media = caps
+        .structure(0)
+        .map(|structure| structure.name().to_string())
+        .unwrap_or_default();
+    let kind = if media.starts_with("video/") || media.starts_with("image/") {
+        Some("video")
+    } else if media.starts_with("audio/") {
+        Some("audio")
+    } else {
+        None
+    };
+
+    let mut elements = vec![ElementFactory::make("queue").build()?];
+    match (kind, mode) {
+        (None, _) => elements.push(ElementFactory::make("fakesink").build()?),
+        (Some("video"), ClipExportMode::Reencode) => {
+            elements.push(ElementFactory::make("videoconvert").build()?);
+            elements.push(ElementFactory::make("x264enc").build()?);
+            elements.push(ElementFactory::make("h264parse").build()?);
+        }
+        (Some(_), ClipExportMode::Reencode) => {
+            elements.push(ElementFactory::make("audioconvert").build()?);
+            elements.push(ElementFactory::make("audioresample").build()?);
+            elements.push(ElementFactory::make("avenc_aac").build()?);
+            elements.push(ElementFactory::make("aacparse").build()?);
+        }
+        (Some(_), ClipExportMode::Copy) => {}
+    }
+    pipeline.add_many(&elements)?;
+    Element::link_many(&elements)?;
+    if let Some(kind) = kind {
+        let mux_pad = muxer
+            .request_pad_simple(&format!("{}_%u", kind))
+            .ok_or_else(|| {
+                RecordError::StreamError(format!("Failed to request muxer pad for {}", media))
+            })?;
+        let last = elements.last().unwrap();
+        let src = last
+            .static_pad("src")
+            .ok_or_else(|| RecordError::StreamError("Failed to get src pad".to_string()))?;
+        src.link(&mux_pad)?;
+    }
+    for element in &elements {
+        element.sync_state_with_parent()?;
+    }
+    let sink_pad = elements[0]
+        .static_pad("sink")
+        .ok_or_else(|| RecordError::StreamError("Failed to get queue sink pad".to_string()))?;
+    src_pad.link(&sink_pad)?;
+    Ok(())
+}
+
+/// 開始・終了位置を検証し、ミリ秒に変換する
+fn validate_request(
+    recording: &Recording,
+    request: &ClipExportRequest,
+) -> Result<(i64, i64), RecordError> {
+    if recording.status != RecordingStatus::Completed {
+        return Err(RecordError::StreamError(format!(
+            "Recording {} is not completed",
+            recording.id
+        )));
+    }
+    if recording.is_segmented() {
+        return Err(RecordError::StreamError(
+            "Clips cannot be exported from segmented recordings".to_string(),
+        ));
+    }
+    // NaNも弾くよう否定で判定する
+    if !(request.start_seconds >= 0.0 && request.start_seconds < request.end_seconds) {
+        return Err(RecordError::StreamError(
+            "start_seconds must be 0 or more and less than end_seconds".to_string(),
+        ));
+    }
+    if let Some(duration_seconds) = recording.duration_seconds {
+        let file_seconds = duration_seconds as f64 + recording.preroll_ms as f64 / 1000.0;
+        if request.start_seconds >= file_seconds {
+            return Err(RecordError::StreamError(format!(
+                "start_seconds must be less than the recording length ({}s)",
+                file_seconds
+            )));
+        }
+    }
+    let to_ms = |seconds: f64| (seconds * 1000.0).round() as i64;
+    Ok((to_ms(request.start_seconds), to_ms(request.end_seconds)))
+}
+
+/// FMP4の録画から書き出したクリップは通常のMP4にする
+fn clip_container(container: RecordingContainer) -> RecordingContainer {
+    match container {
+        RecordingContainer::Mkv => RecordingContainer::Mkv,
+        RecordingContainer::Mp4 | RecordingContainer::Fmp4 => RecordingContainer::Mp4,
+    }
+}
+
+/// 書き出し元の録画と同じディレクトリの {元のファイル名}_clip_{export_id}.{拡張子}
+fn clip_path(parent: &Recording, export_id: Uuid) -> PathBuf {
+    let path = Path::new(&parent.file_path);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| parent.id.to_string());
+    path.with_file_name(format!(
+        "{}_clip_{}.{}",
+        stem,
+        export_id,
+        clip_container(parent.container).extension()
+    ))
+}
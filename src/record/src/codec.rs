@@ -0,0 +1,256 @@
+use crate::error::RecordError;
+use gstreamer::prelude::*;
+use gstreamer::{Element, ElementFactory};
+use serde::{Deserialize, Serialize};
+
+/// 受信する映像のコーデック
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    H264,
+    H265,
+    Mjpeg,
+    Av1,
+}
+
+impl VideoCodec {
+    /// rtspsrcのsrcパッドのcaps（application/x-rtp）からコーデックを判定する
+    pub fn from_rtp_caps(caps: &gstreamer::CapsRef) -> Option<Self> {
+        let structure = caps.structure(0)?;
+        if structure.name() != "application/x-rtp" {
+            return None;
+        }
+        match structure.get::<&str>("encoding-name").ok()? {
+            "H264" => Some(Self::H264),
+            "H265" => Some(Self::H265),
+            "JPEG" => Some(Self::Mjpeg),
+            "AV1" => Some(Self::Av1),
+            _ => None,
+        }
+    }
+
+    /// depay後の符号化済み映像のcapsからコーデックを判定する
+    pub fn from_caps(caps: &gstreamer::CapsRef) -> Option<Self> {
+        match caps.structure(0)?.name().as_str() {
+            "video/x-h264" => Some(Self::H264),
+            "video/x-h265" => Some(Self::H265),
+            "image/jpeg" => Some(Self::Mjpeg),
+            "video/x-av1" => Some(Self::Av1),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::H264 => "h264",
+            Self::H265 => "h265",
+            Self::Mjpeg => "mjpeg",
+            Self::Av1 => "av1",
+        }
+    }
+
+    /// ベースパイプラインのdepayloaderを生成
+    pub fn build_depayloader(&self) -> Result<Element, RecordError> {
+        let depay = match self {
+            Self::H264 => ElementFactory::make("rtph264depay")
+                .property("wait-for-keyframe", true)
+                .build()?,
+            Self::H265 => ElementFactory::make("rtph265depay")
+                .property("wait-for-keyframe", true)
+                .build()?,
+            Self::Mjpeg => ElementFactory::make("rtpjpegdepay").build()?,
+            Self::Av1 => ElementFactory::make("rtpav1depay")
+                .property("wait-for-keyframe", true)
+                .build()?,
+        };
+        Ok(depay)
+    }
+
+    /// ベースパイプラインのparserを生成（SPS/PPSなどを各キーフレームの前に挿入する）
+    pub fn build_parser(&self) -> Result<Element, RecordError> {
+        let parser = match self {
+            Self::H264 => ElementFactory::make("h264parse")
+                .property("config-interval", -1i32)
+                .property("disable-passthrough", true)
+                .build()?,
+            Self::H265 => ElementFactory::make("h265parse")
+                .property("config-interval", -1i32)
+                .property("disable-passthrough", true)
+                .build()?,
+            Self::Mjpeg => ElementFactory::make("jpegparse").build()?,
+            Self::Av1 => ElementFactory::make("av1parse").build()?,
+        };
+        Ok(parser)
+    }
+
+    /// 録画Bin用のparserを生成（muxerが要求するstream-formatに変換する）
+    pub fn build_recording_parser(&self) -> Result<Element, RecordError> {
+        let factory = match self {
+            Self::H264 => "h264parse",
+            Self::H265 => "h265parse",
+            Self::Mjpeg => "jpegparse",
+            Self::Av1 => "av1parse",
+        };
+        Ok(ElementFactory::make(factory).build()?)
+    }
+
+    /// WebRTC配信用のRTP payloaderを生成。ブラウザが受信できないコーデックはエラー
+    pub fn build_webrtc_payloader(&self) -> Result<Element, RecordError> {
+        let payloader = match self {
+            Self::H264 => ElementFactory::make("rtph264pay")
+                .property("config-interval", -1i32)
+                .build()?,
+            Self::H265 => ElementFactory::make("rtph265pay")
+                .property("config-interval", -1i32)
+                .build()?,
+            Self::Av1 => ElementFactory::make("rtpav1pay").build()?,
+            Self::Mjpeg => {
+                return Err(RecordError::StreamError(
+                    "MJPEG streams cannot be sent over WebRTC".to_string(),
+                ))
+            }
+        };
+        payloader.set_property("pt", 96u32);
+        Ok(payloader)
+    }
+}
+
+impl std::fmt::Display for VideoCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// 受信する音声のコーデック
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioCodec {
+    Aac,
+    Opus,
+}
+
+impl AudioCodec {
+    /// rtspsrcのsrcパッドのcaps（application/x-rtp）からコーデックを判定する
+    pub fn from_rtp_caps(caps: &gstreamer::CapsRef) -> Option<Self> {
+        let structure = caps.structure(0)?;
+        if structure.name() != "application/x-rtp" {
+            return None;
+        }
+        match structure.get::<&str>("encoding-name").ok()? {
+            "MPEG4-GENERIC" | "MP4A-LATM" => Some(Self::Aac),
+            "OPUS" => Some(Self::Opus),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Aac => "aac",
+            Self::Opus => "opus",
+        }
+    }
+
+    /// ベースパイプラインのdepayloaderを生成（AACはRTPのペイロード形式で使い分ける）
+    pub fn build_depayloader(&self, caps: &gstreamer::CapsRef) -> Result<Element, RecordError> {
+        let encoding_name = caps
+            .structure(0)
+            .and_then(|structure| structure.get::<&str>("encoding-name").ok());
+        let factory = match (self, encoding_name) {
+            (Self::Aac, Some("MP4A-LATM")) => "rtpmp4adepay",
+            (Self::Aac, _) => "rtpmp4gdepay",
+            (Self::Opus, _) => "rtpopusdepay",
+        };
+        Ok(ElementFactory::make(factory).build()?)
+    }
+
+    /// parserを生成（ベースパイプラインと録画Binの両方で使用する）
+    pub fn build_parser(&self) -> Result<Element, RecordError> {
+        let factory = match self {
+            Self::Aac => "aacparse",
+            Self::Opus => "opusparse",
+        };
+        Ok(ElementFactory::make(factory).build()?)
+    }
+
+    /// WebRTC配信用の要素を生成。AACはブラウザが受信できないためOpusに変換する
+    pub fn build_webrtc_payloader(&self) -> Result<Element, RecordError> {
+        let description = match self {
+            Self::Aac => "decodebin ! audioconvert ! audioresample ! opusenc ! rtpopuspay pt=97",
+            Self::Opus => "rtpopuspay pt=97",
+        };
+        Ok(gstreamer::parse::bin_from_description(description, true)?.upcast())
+    }
+}
+
+impl std::fmt::Display for AudioCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rtp_caps(encoding_name: &str) -> gstreamer::Caps {
+        gstreamer::init().unwrap();
+        gstreamer::Caps::builder("application/x-rtp")
+            .field("media", "video")
+            .field("encoding-name", encoding_name)
+            .build()
+    }
+
+    fn caps(name: &str) -> gstreamer::Caps {
+        gstreamer::init().unwrap();
+        gstreamer::Caps::new_empty_simple(name)
+    }
+
+    #[test]
+    fn video_codec_from_rtp_caps() {
+        assert_eq!(
+            VideoCodec::from_rtp_caps(&rtp_caps("H264")),
+            Some(VideoCodec::H264)
+        );
+        assert_eq!(
+            VideoCodec::from_rtp_caps(&rtp_caps("H265")),
+            Some(VideoCodec::H265)
+        );
+        assert_eq!(
+            VideoCodec::from_rtp_caps(&rtp_caps("JPEG")),
+            Some(VideoCodec::Mjpeg)
+        );
+        assert_eq!(
+            VideoCodec::from_rtp_caps(&rtp_caps("AV1")),
+            Some(VideoCodec::Av1)
+        );
+    }
+
+    #[test]
+    fn unknown_video_codecs_are_rejected() {
+        assert_eq!(VideoCodec::from_rtp_caps(&rtp_caps("VP8")), None);
+        // RTP以外のcapsやencoding-nameのないcapsも判定しない
+        assert_eq!(VideoCodec::from_rtp_caps(&caps("video/x-h264")), None);
+        assert_eq!(VideoCodec::from_rtp_caps(&caps("application/x-rtp")), None);
+        assert_eq!(VideoCodec::from_caps(&caps("video/x-vp8")), None);
+    }
+
+    #[test]
+    fn video_codec_from_encoded_caps() {
+        assert_eq!(
+            VideoCodec::from_caps(&caps("video/x-h264")),
+            Some(VideoCodec::H264)
+        );
+        assert_eq!(
+            VideoCodec::from_caps(&caps("video/x-h265")),
+            Some(VideoCodec::H265)
+        );
+        assert_eq!(
+            VideoCodec::from_caps(&caps("image/jpeg")),
+            Some(VideoCodec::Mjpeg)
+        );
+        assert_eq!(
+            VideoCodec::from_caps(&caps("video/x-av1")),
+            Some(VideoCodec::Av1)
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use crate::error::RecordError;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+/// 暗号化済みの値の接頭辞（バージョン）
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+/// AES-GCMのnonceの長さ（バイト）
+const NONCE_LENGTH: usize = 12;
+
+/// ログやDebug出力に平文が出ないパスワード
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct Password(String);
+
+impl Password {
+    pub fn new(password: impl Into<String>) -> Self {
+        Self(password.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::fmt::Debug for Password {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("\"********\"")
+    }
+}
+
+/// DBに保存する認証情報を暗号化する（AES-256-GCM）。
+/// 鍵が設定されていない場合、認証情報の保存はエラーになる。
+#[derive(Clone)]
+pub struct CredentialCipher {
+    cipher: Option<Aes256Gcm>,
+}
+
+impl CredentialCipher {
+    /// Base64でエンコードされた32バイトの鍵から作成する
+    pub fn new(key: Option<&str>) -> Result<Self, RecordError> {
+        let cipher = match key {
+            Some(key) => {
+                let key = BASE64.decode(key.trim()).map_err(|e| {
+                    RecordError::ConfigError(format!("Invalid credentials_key: {}", e))
+                })?;
+                if key.len() != 32 {
+                    return Err(RecordError::ConfigError(
+                        "credentials_key must be 32 bytes encoded in Base64".to_string(),
+                    ));
+                }
+                Some(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+            }
+            None => None,
+        };
+        Ok(Self { cipher })
+    }
+
+    pub fn encrypt(&self, password: &Password) -> Result<String, RecordError> {
+        let cipher = self.cipher()?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, password.expose().as_bytes())
+            .map_err(|_| RecordError::InternalError("Failed to encrypt credentials".into()))?;
+        let mut payload = nonce.to_vec();
+        payload.extend(ciphertext);
+        Ok(format!("{}{}", ENCRYPTED_PREFIX, BASE64.encode(payload)))
+    }
+
+    /// 暗号化済みの値を復号する。接頭辞のない値（平文）はエラーにする
+    pub fn decrypt(&self, stored: &str) -> Result<Password, RecordError> {
+        let Some(encoded) = stored.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Err(RecordError::InternalError(
+                "Stored credentials are not encrypted".into(),
+            ));
+        };
+        let cipher = self.cipher()?;
+        let payload = BASE64.decode(encoded).map_err(|e| {
+            RecordError::InternalError(format!("Invalid stored credentials: {}", e))
+        })?;
+        if payload.len() < NONCE_LENGTH {
+            return Err(RecordError::InternalError(
+                "Invalid stored credentials".into(),
+            ));
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LENGTH);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| RecordError::InternalError("Failed to decrypt credentials".into()))?;
+        String::from_utf8(plaintext)
+            .map(Password::new)
+            .map_err(|_| RecordError::InternalError("Failed to decrypt credentials".into()))
+    }
+
+    fn cipher(&self) -> Result<&Aes256Gcm, RecordError> {
+        self.cipher.as_ref().ok_or_else(|| {
+            RecordError::ConfigError("security.credentials_key is not configured".to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> String {
+        BASE64.encode([byte; 32])
+    }
+
+    fn cipher(byte: u8) -> CredentialCipher {
+        CredentialCipher::new(Some(&key(byte))).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let cipher = cipher(1);
+        let encrypted = cipher.encrypt(&Password::new("s3cret")).unwrap();
+        assert!(encrypted.starts_with(ENCRYPTED_PREFIX));
+        assert!(!encrypted.contains("s3cret"));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap().expose(), "s3cret");
+    }
+
+    #[test]
+    fn nonce_is_random() {
+        let cipher = cipher(1);
+        let password = Password::new("s3cret");
+        assert_ne!(
+            cipher.encrypt(&password).unwrap(),
+            cipher.encrypt(&password).unwrap()
+        );
+    }
+
+    #[test]
+    fn wrong_key_fails() {
+        let encrypted = cipher(1).encrypt(&Password::new("s3cret")).unwrap();
+        assert!(cipher(2).decrypt(&encrypted).is_err());
+    }
+
+    #[test]
+    fn missing_key_fails() {
+        let encrypted = cipher(1).encrypt(&Password::new("s3cret")).unwrap();
+        let cipher = CredentialCipher::new(None).unwrap();
+        assert!(matches!(
+            cipher.encrypt(&Password::new("s3cret")),
+            Err(RecordError::ConfigError(_))
+        ));
+        assert!(matches!(
+            cipher.decrypt(&encrypted),
+            Err(RecordError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_keys_are_rejected() {
+        assert!(CredentialCipher::new(Some("not base64!")).is_err());
+        assert!(CredentialCipher::new(Some(&BASE64.encode([0u8; 16]))).is_err());
+    }
+
+    #[test]
+    fn plaintext_is_rejected() {
+        assert!(cipher(1).decrypt("s3cret").is_err());
+    }
+
+    #[test]
+    fn truncated_payloads_fail() {
+        let cipher = cipher(1);
+        let encrypted = cipher.encrypt(&Password::new("s3cret")).unwrap();
+        let payload = BASE64
+            .decode(encrypted.strip_prefix(ENCRYPTED_PREFIX).unwrap())
+            .unwrap();
+
+        // nonceより短い
+        let short = format!("{}{}", ENCRYPTED_PREFIX, BASE64.encode(&payload[..8]));
+        assert!(cipher.decrypt(&short).is_err());
+        // 認証タグが欠けている
+        let truncated = format!(
+            "{}{}",
+            ENCRYPTED_PREFIX,
+            BASE64.encode(&payload[..payload.len() - 1])
+        );
+        assert!(cipher.decrypt(&truncated).is_err());
+        assert!(cipher.decrypt(ENCRYPTED_PREFIX).is_err());
+        assert!(cipher
+            .decrypt(&format!("{}not base64!", ENCRYPTED_PREFIX))
+            .is_err());
+    }
+
+    #[test]
+    fn debug_is_redacted() {
+        let password = Password::new("s3cret");
+        assert_eq!(format!("{:?}", password), "\"********\"");
+        assert_eq!(format!("{:?}", Some(password)), "Some(\"********\")");
+    }
+}
@@ -1,9 +1,17 @@
+use crate::credentials::Password;
 use crate::error::RecordError;
-use crate::models::{Recording, RecordingStatus};
+use crate::models::{
+    ClipExport, ClipExportMode, ClipExportStatus, NewRecording, Recording, RecordingPause,
+    RecordingSchedule, RecordingSegment, RecordingStatus, ScheduleRequest, StopReason,
+    StreamProfile, StreamProfileRequest, Webhook, WebhookDelivery, WebhookDeliveryStatus,
+    WebhookRequest,
+};
+use crate::recording::MediaInfo;
 use chrono::{DateTime, Utc};
 use sqlx::{migrate::MigrateDatabase, PgPool, Postgres};
 use uuid::Uuid;
 
+#[derive(Clone)]
 pub struct Database {
     pool: PgPool,
 }
@@ -32,27 +40,45 @@ impl Database {
         self.pool.acquire().await.is_ok()
     }
 
-    pub async fn create_recording(
-        &self,
-        id: Uuid,
-        file_name: String,
-        file_path: String,
-        start_time: DateTime<Utc>,
-    ) -> Result<Recording, RecordError> {
+    /// コネクションプールの接続数とそのうちアイドルの接続数
+    pub fn pool_status(&self) -> (u32, usize) {
+        (self.pool.size(), self.pool.num_idle())
+    }
+
+    pub async fn create_recording(&self, new: &NewRecording) -> Result<Recording, RecordError> {
         let status = RecordingStatus::Recording;
         let recording = sqlx::query_as!(
             Recording,
             r#"
-            INSERT INTO recordings (id, file_name, file_path, start_time, status, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, NOW(), NOW())
+            INSERT INTO recordings (id, file_name, file_path, start_time, status,
+                                    segment_duration_seconds, segment_max_size_bytes,
+                                    container, max_duration_seconds, max_size_bytes,
+                                    stop_at, stream_id, protocol, source_url,
+                                    created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, NOW(), NOW())
             RETURNING id, file_name, file_path, start_time, end_time, duration_seconds, 
-                      file_size_bytes, status AS "status: _", created_at, updated_at
+                      file_size_bytes, status AS "status: _", created_at, updated_at,
+                   segment_duration_seconds, segment_max_size_bytes,
+                   container AS "container: _", preroll_ms,
+                   max_duration_seconds, max_size_bytes, stop_at,
+                   stop_reason AS "stop_reason: _",
+                   stream_id, protocol, source_url, codec, width, height, framerate, audio_codec,
+                   parent_recording_id
             "#,
-            id,
-            file_name,
-            file_path,
-            start_time,
+            new.id,
+            new.file_name,
+            new.file_path,
+            new.start_time,
             status as _,
+            new.segment_duration_seconds,
+            new.segment_max_size_bytes,
+            new.container as _,
+            new.max_duration_seconds,
+            new.max_size_bytes,
+            new.stop_at,
+            new.stream_id,
+            new.protocol,
+            new.source_url,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -66,6 +92,7 @@ impl Database {
         end_time: DateTime<Utc>,
         duration_seconds: i64,
         file_size_bytes: i64,
+        stop_reason: Option<StopReason>,
     ) -> Result<Recording, RecordError> {
         let status = RecordingStatus::Completed;
         let recording = sqlx::query_as!(
@@ -73,16 +100,23 @@ impl Database {
             r#"
             UPDATE recordings 
             SET end_time = $2, duration_seconds = $3, file_size_bytes = $4, 
-                status = $5, updated_at = NOW()
+                status = $5, stop_reason = $6, updated_at = NOW()
             WHERE id = $1
             RETURNING id, file_name, file_path, start_time, end_time, duration_seconds, 
-                      file_size_bytes, status AS "status: _", created_at, updated_at
+                      file_size_bytes, status AS "status: _", created_at, updated_at,
+                   segment_duration_seconds, segment_max_size_bytes,
+                   container AS "container: _", preroll_ms,
+                   max_duration_seconds, max_size_bytes, stop_at,
+                   stop_reason AS "stop_reason: _",
+                   stream_id, protocol, source_url, codec, width, height, framerate, audio_codec,
+                   parent_recording_id
             "#,
             id,
             end_time,
             duration_seconds,
             file_size_bytes,
             status as _,
+            stop_reason as _,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -90,20 +124,40 @@ impl Database {
         Ok(recording)
     }
 
-    #[allow(dead_code)]
-    pub async fn update_recording_failed(&self, id: Uuid) -> Result<Recording, RecordError> {
+    /// 録画を失敗にする。判明している終了時刻・長さ・サイズ・停止理由があれば併せて記録する
+    pub async fn update_recording_failed(
+        &self,
+        id: Uuid,
+        end_time: Option<DateTime<Utc>>,
+        duration_seconds: Option<i64>,
+        file_size_bytes: Option<i64>,
+        stop_reason: Option<StopReason>,
+    ) -> Result<Recording, RecordError> {
         let status = RecordingStatus::Failed;
         let recording = sqlx::query_as!(
             Recording,
             r#"
             UPDATE recordings 
-            SET status = $2, updated_at = NOW()
+            SET status = $2, end_time = COALESCE($3, end_time),
+                duration_seconds = COALESCE($4, duration_seconds),
+                file_size_bytes = COALESCE($5, file_size_bytes),
+                stop_reason = COALESCE($6, stop_reason), updated_at = NOW()
             WHERE id = $1
             RETURNING id, file_name, file_path, start_time, end_time, duration_seconds, 
-                      file_size_bytes, status AS "status: _", created_at, updated_at
+                      file_size_bytes, status AS "status: _", created_at, updated_at,
+                   segment_duration_seconds, segment_max_size_bytes,
+                   container AS "container: _", preroll_ms,
+                   max_duration_seconds, max_size_bytes, stop_at,
+                   stop_reason AS "stop_reason: _",
+                   stream_id, protocol, source_url, codec, width, height, framerate, audio_codec,
+                   parent_recording_id
             "#,
             id,
             status as _,
+            end_time,
+            duration_seconds,
+            file_size_bytes,
+            stop_reason as _,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -111,12 +165,46 @@ impl Database {
         Ok(recording)
     }
 
+    /// 録画開始後に判明したプリロールの長さと映像情報を記録する
+    pub async fn update_recording_started(
+        &self,
+        id: Uuid,
+        preroll_ms: i64,
+        media: &MediaInfo,
+    ) -> Result<(), RecordError> {
+        sqlx::query!(
+            r#"
+            UPDATE recordings
+            SET preroll_ms = $2, codec = $3, width = $4, height = $5, framerate = $6,
+                audio_codec = $7, updated_at = NOW()
+            WHERE id = $1
+            "#,
+            id,
+            preroll_ms,
+            media.codec,
+            media.width,
+            media.height,
+            media.framerate,
+            media.audio_codec,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn get_recording(&self, id: Uuid) -> Result<Recording, RecordError> {
         let recording = sqlx::query_as!(
             Recording,
             r#"
             SELECT id, file_name, file_path, start_time, end_time, duration_seconds, 
-                   file_size_bytes, status AS "status: _", created_at, updated_at
+                   file_size_bytes, status AS "status: _", created_at, updated_at,
+                   segment_duration_seconds, segment_max_size_bytes,
+                   container AS "container: _", preroll_ms,
+                   max_duration_seconds, max_size_bytes, stop_at,
+                   stop_reason AS "stop_reason: _",
+                   stream_id, protocol, source_url, codec, width, height, framerate, audio_codec,
+                   parent_recording_id
             FROM recordings 
             WHERE id = $1
             "#,
@@ -129,15 +217,54 @@ impl Database {
         Ok(recording)
     }
 
-    pub async fn list_recordings(&self) -> Result<Vec<Recording>, RecordError> {
+    /// 録画一覧（stream_idを指定した場合はそのストリームの録画のみ）
+    pub async fn list_recordings(
+        &self,
+        stream_id: Option<&str>,
+    ) -> Result<Vec<Recording>, RecordError> {
         let recordings = sqlx::query_as!(
             Recording,
             r#"
             SELECT id, file_name, file_path, start_time, end_time, duration_seconds, 
-                   file_size_bytes, status as "status: _", created_at, updated_at
+                   file_size_bytes, status as "status: _", created_at, updated_at,
+                   segment_duration_seconds, segment_max_size_bytes,
+                   container AS "container: _", preroll_ms,
+                   max_duration_seconds, max_size_bytes, stop_at,
+                   stop_reason AS "stop_reason: _",
+                   stream_id, protocol, source_url, codec, width, height, framerate, audio_codec,
+                   parent_recording_id
             FROM recordings 
+            WHERE $1::TEXT IS NULL OR stream_id = $1
             ORDER BY start_time DESC
             "#,
+            stream_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(recordings)
+    }
+
+    pub async fn list_recordings_by_status(
+        &self,
+        status: RecordingStatus,
+    ) -> Result<Vec<Recording>, RecordError> {
+        let recordings = sqlx::query_as!(
+            Recording,
+            r#"
+            SELECT id, file_name, file_path, start_time, end_time, duration_seconds, 
+                   file_size_bytes, status as "status: _", created_at, updated_at,
+                   segment_duration_seconds, segment_max_size_bytes,
+                   container AS "container: _", preroll_ms,
+                   max_duration_seconds, max_size_bytes, stop_at,
+                   stop_reason AS "stop_reason: _",
+                   stream_id, protocol, source_url, codec, width, height, framerate, audio_codec,
+                   parent_recording_id
+            FROM recordings 
+            WHERE status = $1
+            ORDER BY start_time
+            "#,
+            status as _,
         )
         .fetch_all(&self.pool)
         .await?;
@@ -145,6 +272,21 @@ impl Database {
         Ok(recordings)
     }
 
+    /// 録画とセグメントとしてDBに登録されている全ファイルパスを返す
+    pub async fn list_recording_file_paths(&self) -> Result<Vec<String>, RecordError> {
+        let paths = sqlx::query_scalar!(
+            r#"
+            SELECT file_path AS "file_path!" FROM recordings
+            UNION ALL
+            SELECT file_path AS "file_path!" FROM recording_segments
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(paths)
+    }
+
     pub async fn delete_recording(&self, id: Uuid) -> Result<(), RecordError> {
         let result = sqlx::query("DELETE FROM recordings WHERE id = $1")
             .bind(id)
@@ -157,4 +299,963 @@ impl Database {
 
         Ok(())
     }
+
+    pub async fn create_segment(
+        &self,
+        recording_id: Uuid,
+        segment_index: i32,
+        file_name: String,
+        file_path: String,
+        start_time: DateTime<Utc>,
+    ) -> Result<RecordingSegment, RecordError> {
+        let segment = sqlx::query_as!(
+            RecordingSegment,
+            r#"
+            INSERT INTO recording_segments (id, recording_id, segment_index, file_name, file_path,
+                                            start_time, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, NOW(), NOW())
+            RETURNING id, recording_id, segment_index, file_name, file_path, start_time,
+                      end_time, duration_seconds, file_size_bytes, created_at, updated_at
+            "#,
+            Uuid::new_v4(),
+            recording_id,
+            segment_index,
+            file_name,
+            file_path,
+            start_time,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(segment)
+    }
+
+    /// 未完了のセグメントを完了にする（既に完了している場合はNoneを返す）
+    pub async fn update_segment_completed(
+        &self,
+        recording_id: Uuid,
+        segment_index: i32,
+        end_time: DateTime<Utc>,
+        file_size_bytes: i64,
+    ) -> Result<Option<RecordingSegment>, RecordError> {
+        let segment = sqlx::query_as!(
+            RecordingSegment,
+            r#"
+            UPDATE recording_segments
+            SET end_time = $3,
+                duration_seconds = EXTRACT(EPOCH FROM ($3 - start_time))::BIGINT,
+                file_size_bytes = $4, updated_at = NOW()
+            WHERE recording_id = $1 AND segment_index = $2 AND end_time IS NULL
+            RETURNING id, recording_id, segment_index, file_name, file_path, start_time,
+                      end_time, duration_seconds, file_size_bytes, created_at, updated_at
+            "#,
+            recording_id,
+            segment_index,
+            end_time,
+            file_size_bytes,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(segment)
+    }
+
+    pub async fn get_segment(
+        &self,
+        recording_id: Uuid,
+        segment_index: i32,
+    ) -> Result<RecordingSegment, RecordError> {
+        let segment = sqlx::query_as!(
+            RecordingSegment,
+            r#"
+            SELECT id, recording_id, segment_index, file_name, file_path, start_time,
+                   end_time, duration_seconds, file_size_bytes, created_at, updated_at
+            FROM recording_segments
+            WHERE recording_id = $1 AND segment_index = $2
+            "#,
+            recording_id,
+            segment_index,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| {
+            RecordError::RecordingNotFound(format!("{} (segment {})", recording_id, segment_index))
+        })?;
+
+        Ok(segment)
+    }
+
+    pub async fn list_segments(
+        &self,
+        recording_id: Uuid,
+    ) -> Result<Vec<RecordingSegment>, RecordError> {
+        let segments = sqlx::query_as!(
+            RecordingSegment,
+            r#"
+            SELECT id, recording_id, segment_index, file_name, file_path, start_time,
+                   end_time, duration_seconds, file_size_bytes, created_at, updated_at
+            FROM recording_segments
+            WHERE recording_id = $1
+            ORDER BY segment_index
+            "#,
+            recording_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(segments)
+    }
+
+    /// password_encryptedは暗号化済みのパスワード（空文字列の場合は保存しない）
+    pub async fn create_profile(
+        &self,
+        request: &StreamProfileRequest,
+        password_encrypted: Option<&str>,
+    ) -> Result<StreamProfile, RecordError> {
+        let timeout_seconds = to_bigint(request.timeout_seconds, "timeout_seconds")?;
+        let preroll_seconds = to_bigint(request.preroll_seconds, "preroll_seconds")?;
+        let segment_duration_seconds =
+            to_bigint(request.segment_duration_seconds, "segment_duration_seconds")?;
+        let segment_max_size_bytes =
+            to_bigint(request.segment_max_size_bytes, "segment_max_size_bytes")?;
+        let profile = sqlx::query_as!(
+            StreamProfile,
+            r#"
+            INSERT INTO stream_profiles (id, name, protocol, url, username,
+                                         password_encrypted, latency_ms, transport,
+                                         timeout_seconds, audio, auto_connect,
+                                         preroll_seconds, container,
+                                         segment_duration_seconds, segment_max_size_bytes,
+                                         created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, NULLIF($6, ''), $7, $8, $9, $10, $11, $12, $13, $14,
+                    $15, NOW(), NOW())
+            RETURNING id, name, protocol, url, username,
+                   password_encrypted AS "password_encrypted: Password", latency_ms,
+                   transport AS "transport: _", timeout_seconds, audio, auto_connect, preroll_seconds,
+                   container AS "container: _", segment_duration_seconds,
+                   segment_max_size_bytes, created_at, updated_at
+            "#,
+            Uuid::new_v4(),
+            request.name,
+            request.protocol,
+            request.url,
+            request.username,
+            password_encrypted,
+            request.latency_ms.unwrap_or(0) as i32,
+            request.transport.unwrap_or_default() as _,
+            timeout_seconds,
+            request.audio,
+            request.auto_connect.unwrap_or(false),
+            preroll_seconds,
+            request.container as _,
+            segment_duration_seconds,
+            segment_max_size_bytes,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(profile)
+    }
+
+    pub async fn get_profile(&self, id: Uuid) -> Result<StreamProfile, RecordError> {
+        let profile = sqlx::query_as!(
+            StreamProfile,
+            r#"
+            SELECT id, name, protocol, url, username,
+                   password_encrypted AS "password_encrypted: Password", latency_ms,
+                   transport AS "transport: _", timeout_seconds, audio, auto_connect, preroll_seconds,
+                   container AS "container: _", segment_duration_seconds,
+                   segment_max_size_bytes, created_at, updated_at
+            FROM stream_profiles
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RecordError::NotFound(format!("Stream profile {} not found", id)))?;
+
+        Ok(profile)
+    }
+
+    pub async fn list_profiles(&self) -> Result<Vec<StreamProfile>, RecordError> {
+        let profiles = sqlx::query_as!(
+            StreamProfile,
+            r#"
+            SELECT id, name, protocol, url, username,
+                   password_encrypted AS "password_encrypted: Password", latency_ms,
+                   transport AS "transport: _", timeout_seconds, audio, auto_connect, preroll_seconds,
+                   container AS "container: _", segment_duration_seconds,
+                   segment_max_size_bytes, created_at, updated_at
+            FROM stream_profiles
+            ORDER BY name
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(profiles)
+    }
+
+    /// password_encryptedがNoneの場合は既存のパスワードを維持し、空文字列の場合は削除する
+    pub async fn update_profile(
+        &self,
+        id: Uuid,
+        request: &StreamProfileRequest,
+        password_encrypted: Option<&str>,
+    ) -> Result<StreamProfile, RecordError> {
+        let timeout_seconds = to_bigint(request.timeout_seconds, "timeout_seconds")?;
+        let preroll_seconds = to_bigint(request.preroll_seconds, "preroll_seconds")?;
+        let segment_duration_seconds =
+            to_bigint(request.segment_duration_seconds, "segment_duration_seconds")?;
+        let segment_max_size_bytes =
+            to_bigint(request.segment_max_size_bytes, "segment_max_size_bytes")?;
+        let profile = sqlx::query_as!(
+            StreamProfile,
+            r#"
+            UPDATE stream_profiles
+            SET name = $2, protocol = $3, url = $4, username = $5,
+                password_encrypted = NULLIF(COALESCE($6, password_encrypted), ''),
+                latency_ms = $7, transport = $8, timeout_seconds = $9, audio = $10,
+                auto_connect = $11, preroll_seconds = $12, container = $13,
+                segment_duration_seconds = $14, segment_max_size_bytes = $15,
+                updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, name, protocol, url, username,
+                   password_encrypted AS "password_encrypted: Password", latency_ms,
+                   transport AS "transport: _", timeout_seconds, audio, auto_connect, preroll_seconds,
+                   container AS "container: _", segment_duration_seconds,
+                   segment_max_size_bytes, created_at, updated_at
+            "#,
+            id,
+            request.name,
+            request.protocol,
+            request.url,
+            request.username,
+            password_encrypted,
+            request.latency_ms.unwrap_or(0) as i32,
+            request.transport.unwrap_or_default() as _,
+            timeout_seconds,
+            request.audio,
+            request.auto_connect.unwrap_or(false),
+            preroll_seconds,
+            request.container as _,
+            segment_duration_seconds,
+            segment_max_size_bytes,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RecordError::NotFound(format!("Stream profile {} not found", id)))?;
+
+        Ok(profile)
+    }
+
+    /// 起動時に自動接続するプロファイル
+    pub async fn list_auto_connect_profiles(&self) -> Result<Vec<StreamProfile>, RecordError> {
+        let profiles = sqlx::query_as!(
+            StreamProfile,
+            r#"
+            SELECT id, name, protocol, url, username,
+                   password_encrypted AS "password_encrypted: Password", latency_ms,
+                   transport AS "transport: _", timeout_seconds, audio, auto_connect, preroll_seconds,
+                   container AS "container: _", segment_duration_seconds,
+                   segment_max_size_bytes, created_at, updated_at
+            FROM stream_profiles
+            WHERE auto_connect
+            ORDER BY name
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(profiles)
+    }
+
+    pub async fn delete_profile(&self, id: Uuid) -> Result<(), RecordError> {
+        let result = sqlx::query("DELETE FROM stream_profiles WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RecordError::NotFound(format!(
+                "Stream profile {} not found",
+                id
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub async fn create_schedule(
+        &self,
+        request: &ScheduleRequest,
+    ) -> Result<RecordingSchedule, RecordError> {
+        let schedule = sqlx::query_as!(
+            RecordingSchedule,
+            r#"
+            INSERT INTO recording_schedules (id, profile_id, name, kind, weekdays, start_time,
+                                             end_time, cron_expression, duration_seconds,
+                                             timezone, label, enabled, catch_up,
+                                             created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, NOW(), NOW())
+            RETURNING id, profile_id, name, kind AS "kind: _", weekdays, start_time, end_time,
+                      cron_expression, duration_seconds, timezone, label, enabled, catch_up,
+                      last_window_start, last_recording_id, created_at, updated_at
+            "#,
+            Uuid::new_v4(),
+            request.profile_id,
+            request.name,
+            request.kind as _,
+            &request.weekdays,
+            request.start_time,
+            request.end_time,
+            request.cron_expression,
+            request.duration_seconds,
+            request.timezone.as_deref().unwrap_or("UTC"),
+            request.label,
+            request.enabled.unwrap_or(true),
+            request.catch_up.unwrap_or(true),
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(schedule)
+    }
+
+    pub async fn get_schedule(&self, id: Uuid) -> Result<RecordingSchedule, RecordError> {
+        let schedule = sqlx::query_as!(
+            RecordingSchedule,
+            r#"
+            SELECT id, profile_id, name, kind AS "kind: _", weekdays, start_time, end_time,
+                   cron_expression, duration_seconds, timezone, label, enabled, catch_up,
+                   last_window_start, last_recording_id, created_at, updated_at
+            FROM recording_schedules
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RecordError::NotFound(format!("Recording schedule {} not found", id)))?;
+
+        Ok(schedule)
+    }
+
+    /// スケジュール一覧（profile_idを指定した場合はそのプロファイルのみ）
+    pub async fn list_schedules(
+        &self,
+        profile_id: Option<Uuid>,
+    ) -> Result<Vec<RecordingSchedule>, RecordError> {
+        let schedules = sqlx::query_as!(
+            RecordingSchedule,
+            r#"
+            SELECT id, profile_id, name, kind AS "kind: _", weekdays, start_time, end_time,
+                   cron_expression, duration_seconds, timezone, label, enabled, catch_up,
+                   last_window_start, last_recording_id, created_at, updated_at
+            FROM recording_schedules
+            WHERE $1::UUID IS NULL OR profile_id = $1
+            ORDER BY name
+            "#,
+            profile_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(schedules)
+    }
+
+    pub async fn list_enabled_schedules(&self) -> Result<Vec<RecordingSchedule>, RecordError> {
+        let schedules = sqlx::query_as!(
+            RecordingSchedule,
+            r#"
+            SELECT id, profile_id, name, kind AS "kind: _", weekdays, start_time, end_time,
+                   cron_expression, duration_seconds, timezone, label, enabled, catch_up,
+                   last_window_start, last_recording_id, created_at, updated_at
+            FROM recording_schedules
+            WHERE enabled
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(schedules)
+    }
+
+    /// スケジュールを更新する。録画枠の定義が変わるためlast_window_startは保持する
+    pub async fn update_schedule(
+        &self,
+        id: Uuid,
+        request: &ScheduleRequest,
+    ) -> Result<RecordingSchedule, RecordError> {
+        let schedule = sqlx::query_as!(
+            RecordingSchedule,
+            r#"
+            UPDATE recording_schedules
+            SET profile_id = $2, name = $3, kind = $4, weekdays = $5, start_time = $6,
+                end_time = $7, cron_expression = $8, duration_seconds = $9, timezone = $10,
+                label = $11, enabled = $12, catch_up = $13, updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, profile_id, name, kind AS "kind: _", weekdays, start_time, end_time,
+                      cron_expression, duration_seconds, timezone, label, enabled, catch_up,
+                      last_window_start, last_recording_id, created_at, updated_at
+            "#,
+            id,
+            request.profile_id,
+            request.name,
+            request.kind as _,
+            &request.weekdays,
+            request.start_time,
+            request.end_time,
+            request.cron_expression,
+            request.duration_seconds,
+            request.timezone.as_deref().unwrap_or("UTC"),
+            request.label,
+            request.enabled.unwrap_or(true),
+            request.catch_up.unwrap_or(true),
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RecordError::NotFound(format!("Recording schedule {} not found", id)))?;
+
+        Ok(schedule)
+    }
+
+    pub async fn delete_schedule(&self, id: Uuid) -> Result<(), RecordError> {
+        let result = sqlx::query("DELETE FROM recording_schedules WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RecordError::NotFound(format!(
+                "Recording schedule {} not found",
+                id
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// スケジューラが処理した録画枠の開始時刻と、その録画枠で開始した録画を記録する
+    pub async fn update_schedule_last_window(
+        &self,
+        id: Uuid,
+        window_start: DateTime<Utc>,
+        recording_id: Option<Uuid>,
+    ) -> Result<(), RecordError> {
+        sqlx::query!(
+            r#"
+            UPDATE recording_schedules
+            SET last_window_start = $2, last_recording_id = $3
+            WHERE id = $1
+            "#,
+            id,
+            window_start,
+            recording_id,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// 一時停止区間を開始する
+    pub async fn create_pause(
+        &self,
+        recording_id: Uuid,
+        paused_at: DateTime<Utc>,
+    ) -> Result<RecordingPause, RecordError> {
+        let pause = sqlx::query_as!(
+            RecordingPause,
+            r#"
+            INSERT INTO recording_pauses (id, recording_id, paused_at, created_at)
+            VALUES ($1, $2, $3, NOW())
+            RETURNING id, recording_id, paused_at, resumed_at, created_at
+            "#,
+            Uuid::new_v4(),
+            recording_id,
+            paused_at,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(pause)
+    }
+
+    /// 未終了の一時停止区間を終了する
+    pub async fn close_pause(
+        &self,
+        recording_id: Uuid,
+        resumed_at: DateTime<Utc>,
+    ) -> Result<(), RecordError> {
+        sqlx::query!(
+            "UPDATE recording_pauses SET resumed_at = $2 WHERE recording_id = $1 AND resumed_at IS NULL",
+            recording_id,
+            resumed_at,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_pauses(
+        &self,
+        recording_id: Uuid,
+    ) -> Result<Vec<RecordingPause>, RecordError> {
+        let pauses = sqlx::query_as!(
+            RecordingPause,
+            r#"
+            SELECT id, recording_id, paused_at, resumed_at, created_at
+            FROM recording_pauses
+            WHERE recording_id = $1
+            ORDER BY paused_at
+            "#,
+            recording_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(pauses)
+    }
+
+    /// secret_encryptedは暗号化済みのシークレット（空文字列の場合は保存しない）
+    pub async fn create_webhook(
+        &self,
+        request: &WebhookRequest,
+        secret_encrypted: Option<&str>,
+    ) -> Result<Webhook, RecordError> {
+        let event_types = webhook_event_types(request);
+        let webhook = sqlx::query_as!(
+            Webhook,
+            r#"
+            INSERT INTO webhooks (id, name, url, secret_encrypted, event_types, stream_id,
+                                  enabled, created_at, updated_at)
+            VALUES ($1, $2, $3, NULLIF($4, ''), $5, $6, $7, NOW(), NOW())
+            RETURNING id, name, url, secret_encrypted, event_types, stream_id, enabled,
+                      created_at, updated_at
+            "#,
+            Uuid::new_v4(),
+            request.name,
+            request.url,
+            secret_encrypted,
+            &event_types,
+            request.stream_id,
+            request.enabled.unwrap_or(true),
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(webhook)
+    }
+
+    pub async fn get_webhook(&self, id: Uuid) -> Result<Webhook, RecordError> {
+        let webhook = sqlx::query_as!(
+            Webhook,
+            r#"
+            SELECT id, name, url, secret_encrypted, event_types, stream_id, enabled,
+                   created_at, updated_at
+            FROM webhooks
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RecordError::NotFound(format!("Webhook {} not found", id)))?;
+
+        Ok(webhook)
+    }
+
+    pub async fn list_webhooks(&self) -> Result<Vec<Webhook>, RecordError> {
+        let webhooks = sqlx::query_as!(
+            Webhook,
+            r#"
+            SELECT id, name, url, secret_encrypted, event_types, stream_id, enabled,
+                   created_at, updated_at
+            FROM webhooks
+            ORDER BY name
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(webhooks)
+    }
+
+    /// イベントを購読している有効なWebhook
+    pub async fn list_subscribed_webhooks(
+        &self,
+        event_type: &str,
+        stream_id: Option<&str>,
+    ) -> Result<Vec<Webhook>, RecordError> {
+        let webhooks = sqlx::query_as!(
+            Webhook,
+            r#"
+            SELECT id, name, url, secret_encrypted, event_types, stream_id, enabled,
+                   created_at, updated_at
+            FROM webhooks
+            WHERE enabled AND $1 = ANY(event_types)
+              AND (stream_id IS NULL OR stream_id = $2)
+            "#,
+            event_type,
+            stream_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(webhooks)
+    }
+
+    /// secret_encryptedがNoneの場合は既存のシークレットを維持し、空文字列の場合は削除する
+    pub async fn update_webhook(
+        &self,
+        id: Uuid,
+        request: &WebhookRequest,
+        secret_encrypted: Option<&str>,
+    ) -> Result<Webhook, RecordError> {
+        let event_types = webhook_event_types(request);
+        let webhook = sqlx::query_as!(
+            Webhook,
+            r#"
+            UPDATE webhooks
+            SET name = $2, url = $3,
+                secret_encrypted = NULLIF(COALESCE($4, secret_encrypted), ''),
+                event_types = $5, stream_id = $6, enabled = $7, updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, name, url, secret_encrypted, event_types, stream_id, enabled,
+                      created_at, updated_at
+            "#,
+            id,
+            request.name,
+            request.url,
+            secret_encrypted,
+            &event_types,
+            request.stream_id,
+            request.enabled.unwrap_or(true),
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RecordError::NotFound(format!("Webhook {} not found", id)))?;
+
+        Ok(webhook)
+    }
+
+    pub async fn delete_webhook(&self, id: Uuid) -> Result<(), RecordError> {
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RecordError::NotFound(format!("Webhook {} not found", id)));
+        }
+
+        Ok(())
+    }
+
+    /// 配信を送信待ち（PENDING）で登録する
+    pub async fn create_webhook_delivery(
+        &self,
+        id: Uuid,
+        webhook_id: Uuid,
+        event_type: &str,
+        payload: &serde_json::Value,
+    ) -> Result<WebhookDelivery, RecordError> {
+        let delivery = sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            INSERT INTO webhook_deliveries (id, webhook_id, event_type, payload, status,
+                                            next_attempt_at, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, 'PENDING', NOW(), NOW(), NOW())
+            RETURNING id, webhook_id, event_type, payload, status AS "status: _", attempts,
+                      response_status, last_error, next_attempt_at, created_at, updated_at
+            "#,
+            id,
+            webhook_id,
+            event_type,
+            payload,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(delivery)
+    }
+
+    /// 試行の結果を記録する
+    pub async fn update_webhook_delivery(
+        &self,
+        id: Uuid,
+        status: WebhookDeliveryStatus,
+        attempts: i32,
+        response_status: Option<i32>,
+        last_error: Option<&str>,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> Result<WebhookDelivery, RecordError> {
+        let delivery = sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            UPDATE webhook_deliveries
+            SET status = $2, attempts = $3, response_status = $4, last_error = $5,
+                next_attempt_at = $6, updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, webhook_id, event_type, payload, status AS "status: _", attempts,
+                      response_status, last_error, next_attempt_at, created_at, updated_at
+            "#,
+            id,
+            status as _,
+            attempts,
+            response_status,
+            last_error,
+            next_attempt_at,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RecordError::NotFound(format!("Webhook delivery {} not found", id)))?;
+
+        Ok(delivery)
+    }
+
+    /// 新しい順の配信履歴
+    pub async fn list_webhook_deliveries(
+        &self,
+        webhook_id: Uuid,
+        status: Option<WebhookDeliveryStatus>,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, RecordError> {
+        let deliveries = sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            SELECT id, webhook_id, event_type, payload, status AS "status: _", attempts,
+                   response_status, last_error, next_attempt_at, created_at, updated_at
+            FROM webhook_deliveries
+            WHERE webhook_id = $1 AND ($2::webhook_delivery_status IS NULL OR status = $2)
+            ORDER BY created_at DESC
+            LIMIT $3
+            "#,
+            webhook_id,
+            status as _,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    /// 送信待ちの配信（再起動前に完了しなかったもの）
+    pub async fn list_pending_webhook_deliveries(
+        &self,
+    ) -> Result<Vec<WebhookDelivery>, RecordError> {
+        let deliveries = sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            SELECT id, webhook_id, event_type, payload, status AS "status: _", attempts,
+                   response_status, last_error, next_attempt_at, created_at, updated_at
+            FROM webhook_deliveries
+            WHERE status = 'PENDING'
+            ORDER BY next_attempt_at
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    /// 書き出したクリップを完了済みの録画として登録する
+    pub async fn create_clip_recording(&self, clip: &Recording) -> Result<Recording, RecordError> {
+        let status = RecordingStatus::Completed;
+        let recording = sqlx::query_as!(
+            Recording,
+            r#"
+            INSERT INTO recordings (id, file_name, file_path, start_time, end_time,
+                                    duration_seconds, file_size_bytes, status, container,
+                                    stream_id, protocol, source_url, codec, width, height,
+                                    framerate, audio_codec, parent_recording_id,
+                                    created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
+                    $18, NOW(), NOW())
+            RETURNING id, file_name, file_path, start_time, end_time, duration_seconds,
+                      file_size_bytes, status AS "status: _", created_at, updated_at,
+                   segment_duration_seconds, segment_max_size_bytes,
+                   container AS "container: _", preroll_ms,
+                   max_duration_seconds, max_size_bytes, stop_at,
+                   stop_reason AS "stop_reason: _",
+                   stream_id, protocol, source_url, codec, width, height, framerate, audio_codec,
+                   parent_recording_id
+            "#,
+            clip.id,
+            clip.file_name,
+            clip.file_path,
+            clip.start_time,
+            clip.end_time,
+            clip.duration_seconds,
+            clip.file_size_bytes,
+            status as _,
+            clip.container as _,
+            clip.stream_id,
+            clip.protocol,
+            clip.source_url,
+            clip.codec,
+            clip.width,
+            clip.height,
+            clip.framerate,
+            clip.audio_codec,
+            clip.parent_recording_id,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(recording)
+    }
+
+    pub async fn create_clip_export(
+        &self,
+        recording_id: Uuid,
+        start_offset_ms: i64,
+        end_offset_ms: i64,
+        mode: ClipExportMode,
+    ) -> Result<ClipExport, RecordError> {
+        let export = sqlx::query_as!(
+            ClipExport,
+            r#"
+            INSERT INTO clip_exports (id, recording_id, start_offset_ms, end_offset_ms, mode,
+                                      status, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, 'PENDING', NOW(), NOW())
+            RETURNING id, recording_id, clip_recording_id, start_offset_ms, end_offset_ms,
+                      mode AS "mode: _", status AS "status: _", progress, error,
+                      created_at, updated_at, completed_at
+            "#,
+            Uuid::new_v4(),
+            recording_id,
+            start_offset_ms,
+            end_offset_ms,
+            mode as _,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(export)
+    }
+
+    pub async fn get_clip_export(&self, id: Uuid) -> Result<ClipExport, RecordError> {
+        let export = sqlx::query_as!(
+            ClipExport,
+            r#"
+            SELECT id, recording_id, clip_recording_id, start_offset_ms, end_offset_ms,
+                   mode AS "mode: _", status AS "status: _", progress, error,
+                   created_at, updated_at, completed_at
+            FROM clip_exports
+            WHERE id = $1
+            "#,
+            id,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RecordError::NotFound(format!("Clip export {} not found", id)))?;
+
+        Ok(export)
+    }
+
+    /// 録画から書き出したクリップのジョブ（新しい順）
+    pub async fn list_clip_exports(
+        &self,
+        recording_id: Uuid,
+    ) -> Result<Vec<ClipExport>, RecordError> {
+        let exports = sqlx::query_as!(
+            ClipExport,
+            r#"
+            SELECT id, recording_id, clip_recording_id, start_offset_ms, end_offset_ms,
+                   mode AS "mode: _", status AS "status: _", progress, error,
+                   created_at, updated_at, completed_at
+            FROM clip_exports
+            WHERE recording_id = $1
+            ORDER BY created_at DESC
+            "#,
+            recording_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(exports)
+    }
+
+    /// ジョブの状態を更新する。COMPLETED / FAILEDの場合はcompleted_atを記録する
+    pub async fn update_clip_export(
+        &self,
+        id: Uuid,
+        status: ClipExportStatus,
+        progress: f64,
+        error: Option<&str>,
+        clip_recording_id: Option<Uuid>,
+    ) -> Result<(), RecordError> {
+        sqlx::query!(
+            r#"
+            UPDATE clip_exports
+            SET status = $2, progress = $3, error = $4, clip_recording_id = $5,
+                completed_at = CASE WHEN $2::clip_export_status IN ('COMPLETED', 'FAILED') THEN NOW() END,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+            id,
+            status as _,
+            progress,
+            error,
+            clip_recording_id,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_clip_export_progress(
+        &self,
+        id: Uuid,
+        progress: f64,
+    ) -> Result<(), RecordError> {
+        sqlx::query!(
+            "UPDATE clip_exports SET progress = $2, updated_at = NOW() WHERE id = $1",
+            id,
+            progress,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// 前回の異常終了で実行中・実行待ちのまま残ったジョブを失敗にする
+    pub async fn fail_interrupted_clip_exports(&self) -> Result<u64, RecordError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE clip_exports
+            SET status = 'FAILED', error = 'Interrupted by service restart',
+                completed_at = NOW(), updated_at = NOW()
+            WHERE status IN ('PENDING', 'RUNNING')
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// u64の値をBIGINT列に格納できる値に変換する（範囲外はリクエストエラー）
+pub fn to_bigint(value: Option<u64>, field: &str) -> Result<Option<i64>, RecordError> {
+    value
+        .map(i64::try_from)
+        .transpose()
+        .map_err(|_| RecordError::StreamError(format!("{} is out of range", field)))
+}
+
+fn webhook_event_types(request: &WebhookRequest) -> Vec<String> {
+    request
+        .event_types
+        .iter()
+        .map(|event_type| event_type.as_str().to_string())
+        .collect()
 }
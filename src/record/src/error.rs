@@ -24,6 +24,9 @@ pub enum RecordError {
     #[error("Recording not found: {0}")]
     RecordingNotFound(String),
 
+    #[error("Not found: {0}")]
+    NotFound(String),
+
     // #[error("Already recording")]
     // AlreadyRecording, // 未使用のためコメントアウト
 
@@ -37,6 +40,9 @@ pub enum RecordError {
 
     #[error("Internal server error: {0}")]
     InternalError(String),
+
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
 }
 
 impl IntoResponse for RecordError {
@@ -61,6 +67,7 @@ impl IntoResponse for RecordError {
                 "RESOURCE_NOT_FOUND",
                 format!("Recording with ID {} not found", id),
             ),
+            RecordError::NotFound(msg) => (StatusCode::NOT_FOUND, "RESOURCE_NOT_FOUND", msg),
             // RecordError::AlreadyRecording => (
             //     StatusCode::CONFLICT,
             //     "ALREADY_RECORDING",
@@ -86,6 +93,9 @@ impl IntoResponse for RecordError {
                 "INTERNAL_SERVER_ERROR",
                 msg,
             ),
+            RecordError::Unavailable(msg) => {
+                (StatusCode::SERVICE_UNAVAILABLE, "SERVICE_UNAVAILABLE", msg)
+            }
         };
 
         let body = Json(json!({
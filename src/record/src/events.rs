@@ -0,0 +1,136 @@
+use crate::models::StopReason;
+use crate::stream::StreamId;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc};
+
+/// 購読者ごとに保持するイベントの数（超えた分は古い順に失われる）
+const EVENT_BUS_CAPACITY: usize = 256;
+
+/// ストリームと録画のイベント
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub stream_id: Option<StreamId>,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    StreamConnected,
+    StreamStalled,
+    /// ストリームが途絶え、再接続を待機している
+    StreamReconnecting {
+        attempt: u32,
+        reason: String,
+    },
+    StreamReconnected,
+    /// 再接続を諦めた
+    StreamFailed {
+        reason: String,
+    },
+    StreamDisconnected,
+    /// パイプライン（受信側または録画Bin）のエラー
+    PipelineError {
+        message: String,
+        source: String,
+    },
+    RecordingStarted {
+        recording_id: String,
+    },
+    /// セグメント録画で次のセグメントに切り替わった
+    RecordingSegmentRolled {
+        recording_id: String,
+        segment_index: i32,
+        file_name: String,
+    },
+    RecordingStopped {
+        recording_id: String,
+        stop_reason: StopReason,
+    },
+    /// 録画の開始または停止（ファイルの確定）に失敗した
+    RecordingFailed {
+        recording_id: String,
+        message: String,
+    },
+}
+
+/// アプリケーション内のイベントを購読者（WebSocket / SSE）に配信する
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+    /// イベントを取りこぼせない購読者（Webhookの配信）
+    lossless: Arc<Mutex<Vec<mpsc::UnboundedSender<Event>>>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        Self {
+            sender,
+            lossless: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// イベントを配信する（購読者がいない場合は破棄される）
+    pub fn publish(&self, stream_id: Option<&str>, kind: EventKind) {
+        let event = Event {
+            stream_id: stream_id.map(str::to_string),
+            timestamp: Utc::now(),
+            kind,
+        };
+        self.lossless
+            .lock()
+            .unwrap()
+            .retain(|sender| sender.send(event.clone()).is_ok());
+        let _ = self.sender.send(event);
+    }
+
+    /// 購読する。受信が遅れてEVENT_BUS_CAPACITYを超えた分は失われる
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    /// 取りこぼさずに購読する（上限のないキューに溜めるため、受信し続ける購読者のみが使う）
+    pub fn subscribe_lossless(&self) -> mpsc::UnboundedReceiver<Event> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.lossless.lock().unwrap().push(sender);
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lossless_subscribers_receive_every_event() {
+        let events = EventBus::new();
+        let mut receiver = events.subscribe_lossless();
+        // broadcastの容量を超えても失われない
+        for attempt in 1..=1000 {
+            events.publish(
+                Some("stream"),
+                EventKind::StreamReconnecting {
+                    attempt,
+                    reason: "timeout".to_string(),
+                },
+            );
+        }
+        for expected in 1..=1000 {
+            match receiver.try_recv().unwrap().kind {
+                EventKind::StreamReconnecting { attempt, .. } => assert_eq!(attempt, expected),
+                kind => panic!("unexpected event: {:?}", kind),
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// 最後に映像が届いた時刻（identityのhandoffで更新する）と、
+/// 録画を開始できる状態か（teeに最初のキーフレームが届いたか）
+#[derive(Debug, Clone)]
+pub struct DataWatch {
+    last_data: Arc<Mutex<Option<Instant>>>,
+    ready: Arc<watch::Sender<bool>>,
+}
+
+impl Default for DataWatch {
+    fn default() -> Self {
+        Self {
+            last_data: Arc::default(),
+            ready: Arc::new(watch::Sender::new(false)),
+        }
+    }
+}
+
+impl DataWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 映像が届いたことを記録する
+    pub fn touch(&self) {
+        *self.last_data.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// teeにキーフレームが届いたことを記録する
+    pub fn keyframe(&self) {
+        self.ready
+            .send_if_modified(|ready| !std::mem::replace(ready, true));
+    }
+
+    /// 受信側を作り直す際に、映像もキーフレームも届いていない状態に戻す
+    pub fn reset(&self) {
+        *self.last_data.lock().unwrap() = None;
+        self.ready.send_replace(false);
+    }
+
+    pub fn last_data(&self) -> Option<Instant> {
+        *self.last_data.lock().unwrap()
+    }
+
+    /// 最後に映像が届いてからの経過時間（ミリ秒）
+    pub fn last_data_age_ms(&self) -> Option<u64> {
+        self.last_data()
+            .map(|last_data| last_data.elapsed().as_millis() as u64)
+    }
+
+    pub fn is_ready(&self) -> bool {
+        *self.ready.borrow()
+    }
+
+    /// 最初のキーフレームが届くまで最大timeoutだけ待機する。届いた場合はtrueを返す
+    pub async fn wait_ready(&self, timeout: Duration) -> bool {
+        let mut receiver = self.ready.subscribe();
+        let result = tokio::time::timeout(timeout, receiver.wait_for(|ready| *ready)).await;
+        matches!(result, Ok(Ok(_)))
+    }
+}
+
+/// attempt回目（1始まり）の再試行までの待機時間。失敗するごとに倍にし、上限で頭打ちにする
+pub fn backoff_delay(initial_seconds: u64, max_seconds: u64, attempt: u32) -> Duration {
+    let initial = initial_seconds.max(1);
+    let exponent = attempt.saturating_sub(1).min(16);
+    let seconds = initial.saturating_mul(1 << exponent);
+    Duration::from_secs(seconds.min(max_seconds.max(initial)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_from_the_initial_delay() {
+        assert_eq!(backoff_delay(5, 300, 1), Duration::from_secs(5));
+        assert_eq!(backoff_delay(5, 300, 2), Duration::from_secs(10));
+        assert_eq!(backoff_delay(5, 300, 3), Duration::from_secs(20));
+        // attemptが0でも初期値を返す
+        assert_eq!(backoff_delay(5, 300, 0), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn backoff_is_capped_at_the_maximum() {
+        assert_eq!(backoff_delay(5, 300, 7), Duration::from_secs(300));
+        assert_eq!(backoff_delay(5, 300, u32::MAX), Duration::from_secs(300));
+        // 上限が初期値より小さい場合は初期値で頭打ちにする
+        assert_eq!(backoff_delay(10, 3, 4), Duration::from_secs(10));
+        // 初期値0は1秒として扱う
+        assert_eq!(backoff_delay(0, 300, 1), Duration::from_secs(1));
+    }
+}
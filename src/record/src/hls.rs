@@ -0,0 +1,177 @@
+use crate::codec::VideoCodec;
+use crate::config::HlsConfig;
+use crate::error::RecordError;
+use crate::stream::build_viewer_queue;
+use gstreamer::prelude::*;
+use gstreamer::{Element, ElementFactory, Pad, Pipeline};
+use std::path::{Path, PathBuf};
+
+/// HLSの分岐の要素の名前の接頭辞（バスのエラーを受信側のエラーと区別する）
+pub const HLS_ELEMENT_PREFIX: &str = "hls-";
+
+/// プレイリストのファイル名
+pub const HLS_PLAYLIST_NAME: &str = "playlist.m3u8";
+
+/// プレイリストから外れた後もディスクに残すセグメントの数（取得中のクライアント用）
+const HLS_EXTRA_FILES: u32 = 2;
+
+/// ストリームのHLS出力。tee→queue→parse→hlscmafsinkの分岐を持つ
+#[derive(Debug, Clone)]
+pub struct HlsOutput {
+    /// プレイリストとセグメントを書き出すディレクトリ
+    pub directory: PathBuf,
+    tee_pad: Pad,
+    elements: Vec<Element>,
+}
+
+/// teeからHLS（fMP4セグメント）の分岐を作成する。前回の出力が残っている場合は削除する
+pub fn start_hls_output(
+    pipeline: &Pipeline,
+    tee: &Element,
+    codec: VideoCodec,
+    directory: PathBuf,
+    config: &HlsConfig,
+) -> Result<HlsOutput, RecordError> {
+    if codec == VideoCodec::Mjpeg {
+        return Err(RecordError::StreamError(
+            "MJPEG streams cannot be sent over HLS".to_string(),
+        ));
+    }
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory)?;
+
+    let queue = build_viewer_queue(Some(&format!("{}queue", HLS_ELEMENT_PREFIX)))?;
+    // cmafmuxが要求するstream-formatに変換する
+    let parser = codec.build_recording_parser()?;
+    let sink = ElementFactory::make("hlscmafsink")
+        .name(format!("{}sink", HLS_ELEMENT_PREFIX))
+        .property(
+            "playlist-location",
+            path_string(&directory, HLS_PLAYLIST_NAME),
+        )
+        .property("init-location", path_string(&directory, "init%05d.mp4"))
+        .property("location", path_string(&directory, "segment%05d.m4s"))
+        .property("target-duration", config.segment_duration_seconds)
+        .property("playlist-length", config.playlist_length)
+        .property("max-files", config.playlist_length + HLS_EXTRA_FILES)
+        .property("sync", false)
+        .build()
+        .map_err(|_| {
+            RecordError::StreamError(
+                "Failed to create hlscmafsink (gst-plugins-rs hlssink3 is required)".to_string(),
+            )
+        })?;
+    let elements = vec![queue, parser, sink];
+    pipeline.add_many(&elements)?;
+
+    let Some(tee_pad) = tee.request_pad_simple("src_%u") else {
+        remove_elements(pipeline, &elements);
+        return Err(RecordError::StreamError(
+            "Failed to request tee src pad".to_string(),
+        ));
+    };
+    let output = HlsOutput {
+        directory,
+        tee_pad,
+        elements,
+    };
+    if let Err(e) = link_output(&output) {
+        stop_hls_output(pipeline, &output);
+        return Err(e);
+    }
+    Ok(output)
+}
+
+fn link_output(output: &HlsOutput) -> Result<(), RecordError> {
+    Element::link_many(&output.elements)?;
+    for element in &output.elements {
+        element.sync_state_with_parent()?;
+    }
+
+    // セグメントがキーフレームから始まるよう、最初のキーフレームまでのバッファを破棄する
+    output
+        .tee_pad
+        .add_probe(gstreamer::PadProbeType::BUFFER, |_, info| {
+            match info.buffer() {
+                Some(buffer) if buffer.flags().contains(gstreamer::BufferFlags::DELTA_UNIT) => {
+                    gstreamer::PadProbeReturn::Drop
+                }
+                _ => gstreamer::PadProbeReturn::Remove,
+            }
+        });
+    let queue_sink_pad = output.elements[0]
+        .static_pad("sink")
+        .ok_or_else(|| RecordError::StreamError("Failed to get queue sink pad".to_string()))?;
+    output.tee_pad.link(&queue_sink_pad)?;
+    Ok(())
+}
+
+/// 分岐をteeから切り離して要素を削除し、書き出したファイルを削除する
+pub fn stop_hls_output(pipeline: &Pipeline, output: &HlsOutput) {
+    if let Some(peer) = output.tee_pad.peer() {
+        let _ = output.tee_pad.unlink(&peer);
+    }
+    remove_elements(pipeline, &output.elements);
+    if let Some(tee) = output.tee_pad.parent_element() {
+        tee.release_request_pad(&output.tee_pad);
+    }
+    let _ = std::fs::remove_dir_all(&output.directory);
+}
+
+fn remove_elements(pipeline: &Pipeline, elements: &[Element]) {
+    for element in elements {
+        let _ = element.set_state(gstreamer::State::Null);
+        let _ = pipeline.remove(element);
+    }
+}
+
+fn path_string(directory: &Path, file_name: &str) -> String {
+    directory.join(file_name).to_string_lossy().into_owned()
+}
+
+/// 配信するファイルのContent-Type。プレイリストとセグメント以外のファイル名はNone
+pub fn hls_content_type(file_name: &str) -> Option<&'static str> {
+    let valid = !file_name.starts_with('.')
+        && file_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-');
+    if !valid {
+        return None;
+    }
+    match Path::new(file_name).extension()?.to_str()? {
+        "m3u8" => Some("application/vnd.apple.mpegurl"),
+        "m4s" => Some("video/iso.segment"),
+        "mp4" => Some("video/mp4"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playlist_and_segments_have_a_content_type() {
+        assert_eq!(
+            hls_content_type("index.m3u8"),
+            Some("application/vnd.apple.mpegurl")
+        );
+        assert_eq!(
+            hls_content_type("segment_00001.m4s"),
+            Some("video/iso.segment")
+        );
+        assert_eq!(hls_content_type("init-0.mp4"), Some("video/mp4"));
+    }
+
+    #[test]
+    fn rejects_paths_hidden_files_and_unknown_extensions() {
+        assert_eq!(hls_content_type(".."), None);
+        assert_eq!(hls_content_type("../index.m3u8"), None);
+        assert_eq!(hls_content_type(".index.m3u8"), None);
+        assert_eq!(hls_content_type("live/index.m3u8"), None);
+        assert_eq!(hls_content_type("live\\index.m3u8"), None);
+        assert_eq!(hls_content_type("segment.ts"), None);
+        assert_eq!(hls_content_type("index"), None);
+        assert_eq!(hls_content_type(""), None);
+    }
+}
@@ -1,11 +1,23 @@
 pub mod api;
 pub mod app;
+pub mod clips;
+pub mod codec;
 pub mod config;
+pub mod credentials;
 pub mod database;
 pub mod error;
+pub mod events;
+pub mod health;
+pub mod hls;
+pub mod metrics;
 pub mod models;
+pub mod preroll;
+pub mod reconcile;
 pub mod recording;
+pub mod scheduler;
+pub mod stats;
 pub mod stream;
+pub mod webhooks;
 pub mod webrtc;
 
 pub use self::recording::*;
@@ -1,11 +1,23 @@
 mod api;
 mod app;
+mod clips;
+mod codec;
 mod config;
+mod credentials;
 mod database;
 mod error;
+mod events;
+mod health;
+mod hls;
+mod metrics;
 mod models;
+mod preroll;
+mod reconcile;
 mod recording;
+mod scheduler;
+mod stats;
 mod stream;
+mod webhooks;
 mod webrtc;
 
 use anyhow::Result;
@@ -73,7 +85,34 @@ async fn main() -> Result<()> {
     })?;
 
     // Initialize application state
-    let app_state = Arc::new(app::AppState::new(config, database));
+    let app_state = Arc::new(app::AppState::new(config, database).map_err(|e| {
+        error!("Failed to initialize application state: {}", e);
+        e
+    })?);
+
+    // 前回の異常終了でRECORDINGのまま残った録画と、DBに存在しないファイルを整理
+    if let Err(e) = reconcile::reconcile_recordings(&app_state.config, &app_state.database).await {
+        error!("Failed to reconcile recordings: {}", e);
+    }
+
+    // 前回の異常終了で完了しなかったクリップの書き出しを失敗にする
+    if let Err(e) = app_state.clips.fail_interrupted().await {
+        error!("Failed to fail interrupted clip exports: {}", e);
+    }
+
+    // Webhookの配信を開始（自動接続のイベントを取りこぼさないよう接続より先に購読する）
+    app_state.webhooks.spawn(&app_state.events);
+
+    // auto_connectが有効なストリームプロファイルに接続（接続の完了を待たずにサーバーを起動する）
+    let app_state2 = app_state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = app_state2.connect_auto_connect_profiles().await {
+            error!("Failed to auto-connect stream profiles: {}", e);
+        }
+    });
+
+    // 録画スケジュールの実行を開始
+    scheduler::spawn_scheduler(app_state.clone());
 
     // Start the server
     api::serve(app_state).await.map_err(|e| {
@@ -0,0 +1,128 @@
+use crate::error::RecordError;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+/// Prometheus形式で公開するメトリクス。ゲージの多くは/metricsの取得時に現在の状態から設定する
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    pub streams_connected: IntGauge,
+    pub recordings_active: IntGauge,
+    pub recording_bytes_written: IntCounter,
+    pub recording_finalize_duration: Histogram,
+    pub recording_finalize_failures: IntCounter,
+    pub stream_ingest_bitrate: IntGaugeVec,
+    pub stream_reconnects: IntCounterVec,
+    pub webrtc_sessions: IntGauge,
+    pub db_pool_connections: IntGauge,
+    pub db_pool_idle_connections: IntGauge,
+    pub http_request_duration: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, RecordError> {
+        let registry = Registry::new_custom(Some("record".to_string()), None)
+            .map_err(|e| RecordError::InternalError(e.to_string()))?;
+        let metrics = Self {
+            streams_connected: IntGauge::new("streams_connected", "Number of connected streams")
+                .map_err(metrics_error)?,
+            recordings_active: IntGauge::new("recordings_active", "Number of active recordings")
+                .map_err(metrics_error)?,
+            recording_bytes_written: IntCounter::new(
+                "recording_bytes_written_total",
+                "Bytes written by finalized recordings",
+            )
+            .map_err(metrics_error)?,
+            recording_finalize_duration: Histogram::with_opts(
+                HistogramOpts::new(
+                    "recording_finalize_duration_seconds",
+                    "Time taken to stop a recording and finalize its file",
+                )
+                .buckets(vec![0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+            )
+            .map_err(metrics_error)?,
+            recording_finalize_failures: IntCounter::new(
+                "recording_finalize_failures_total",
+                "Recordings that failed to stop or finalize",
+            )
+            .map_err(metrics_error)?,
+            stream_ingest_bitrate: IntGaugeVec::new(
+                Opts::new(
+                    "stream_ingest_bitrate_bps",
+                    "Received video bitrate per stream (bit/s)",
+                ),
+                &["stream_id"],
+            )
+            .map_err(metrics_error)?,
+            stream_reconnects: IntCounterVec::new(
+                Opts::new(
+                    "stream_reconnects_total",
+                    "Reconnect attempts per stream after the stream was lost",
+                ),
+                &["stream_id"],
+            )
+            .map_err(metrics_error)?,
+            webrtc_sessions: IntGauge::new(
+                "webrtc_sessions",
+                "Number of WebRTC (WHEP) viewer sessions",
+            )
+            .map_err(metrics_error)?,
+            db_pool_connections: IntGauge::new(
+                "db_pool_connections",
+                "Open database connections in the pool",
+            )
+            .map_err(metrics_error)?,
+            db_pool_idle_connections: IntGauge::new(
+                "db_pool_idle_connections",
+                "Idle database connections in the pool",
+            )
+            .map_err(metrics_error)?,
+            http_request_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "http_request_duration_seconds",
+                    "HTTP request latency by route",
+                ),
+                &["method", "route", "status"],
+            )
+            .map_err(metrics_error)?,
+            registry,
+        };
+        metrics.register()?;
+        Ok(metrics)
+    }
+
+    fn register(&self) -> Result<(), RecordError> {
+        let collectors: [Box<dyn prometheus::core::Collector>; 11] = [
+            Box::new(self.streams_connected.clone()),
+            Box::new(self.recordings_active.clone()),
+            Box::new(self.recording_bytes_written.clone()),
+            Box::new(self.recording_finalize_duration.clone()),
+            Box::new(self.recording_finalize_failures.clone()),
+            Box::new(self.stream_ingest_bitrate.clone()),
+            Box::new(self.stream_reconnects.clone()),
+            Box::new(self.webrtc_sessions.clone()),
+            Box::new(self.db_pool_connections.clone()),
+            Box::new(self.db_pool_idle_connections.clone()),
+            Box::new(self.http_request_duration.clone()),
+        ];
+        for collector in collectors {
+            self.registry.register(collector).map_err(metrics_error)?;
+        }
+        Ok(())
+    }
+
+    /// Prometheusのテキスト形式で出力する
+    pub fn render(&self) -> Result<String, RecordError> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(metrics_error)?;
+        String::from_utf8(buffer).map_err(|e| RecordError::InternalError(e.to_string()))
+    }
+}
+
+fn metrics_error(err: prometheus::Error) -> RecordError {
+    RecordError::InternalError(format!("Metrics error: {}", err))
+}
@@ -1,5 +1,7 @@
+use crate::codec::{AudioCodec, VideoCodec};
+use crate::credentials::Password;
 use crate::stream::StreamId;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
@@ -16,6 +18,78 @@ pub struct Recording {
     pub status: RecordingStatus,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub segment_duration_seconds: Option<i64>,
+    pub segment_max_size_bytes: Option<i64>,
+    pub container: RecordingContainer,
+    pub preroll_ms: i64,
+    pub max_duration_seconds: Option<i64>,
+    pub max_size_bytes: Option<i64>,
+    pub stop_at: Option<DateTime<Utc>>,
+    pub stop_reason: Option<StopReason>,
+    /// 録画元のストリームID
+    pub stream_id: Option<StreamId>,
+    pub protocol: Option<String>,
+    /// 録画元のURL（認証情報は除去済み）
+    pub source_url: Option<String>,
+    /// 録画開始時にネゴシエーションされた映像の形式
+    pub codec: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub framerate: Option<f64>,
+    /// 録画に含めた音声のコーデック（音声なしの場合はNone）
+    pub audio_codec: Option<String>,
+    /// クリップの書き出し元の録画（ストリームから録画した場合はNone）
+    pub parent_recording_id: Option<Uuid>,
+}
+
+impl Recording {
+    /// セグメント録画かどうか（file_pathはセグメントを格納するディレクトリを指す）
+    pub fn is_segmented(&self) -> bool {
+        self.segment_duration_seconds.is_some() || self.segment_max_size_bytes.is_some()
+    }
+}
+
+/// 録画レコード作成時のパラメータ
+#[derive(Debug, Clone)]
+pub struct NewRecording {
+    pub id: Uuid,
+    pub file_name: String,
+    pub file_path: String,
+    pub start_time: DateTime<Utc>,
+    pub segment_duration_seconds: Option<i64>,
+    pub segment_max_size_bytes: Option<i64>,
+    pub container: RecordingContainer,
+    pub max_duration_seconds: Option<i64>,
+    pub max_size_bytes: Option<i64>,
+    pub stop_at: Option<DateTime<Utc>>,
+    pub stream_id: StreamId,
+    pub protocol: Option<String>,
+    pub source_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RecordingSegment {
+    pub id: Uuid,
+    pub recording_id: Uuid,
+    pub segment_index: i32,
+    pub file_name: String,
+    pub file_path: String,
+    pub start_time: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub duration_seconds: Option<i64>,
+    pub file_size_bytes: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 録画の一時停止区間（resumed_atがNoneの場合は一時停止中）
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RecordingPause {
+    pub id: Uuid,
+    pub recording_id: Uuid,
+    pub paused_at: DateTime<Utc>,
+    pub resumed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
@@ -26,10 +100,90 @@ pub enum RecordingStatus {
     Failed,
 }
 
+/// 録画ファイルのコンテナ形式。
+/// FMP4/MKVは途中で異常終了しても書き込み済みの部分が再生可能
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "recording_container", rename_all = "UPPERCASE")]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingContainer {
+    #[default]
+    Mp4,
+    Fmp4,
+    Mkv,
+}
+
+/// 録画が停止した理由
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
+#[sqlx(
+    type_name = "recording_stop_reason",
+    rename_all = "SCREAMING_SNAKE_CASE"
+)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// 停止APIまたはストリーム切断による停止
+    Manual,
+    /// max_duration_secondsに到達
+    DurationLimit,
+    /// max_size_bytesに到達
+    SizeLimit,
+    /// stop_atの時刻に到達
+    Schedule,
+    /// ストリームからの受信が途絶えた
+    StreamLost,
+    /// サービスの停止で中断され、起動時の整合性チェックで回復した
+    ServiceRestart,
+}
+
+impl RecordingContainer {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordingContainer::Mp4 | RecordingContainer::Fmp4 => "mp4",
+            RecordingContainer::Mkv => "mkv",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            RecordingContainer::Mp4 | RecordingContainer::Fmp4 => "video/mp4",
+            RecordingContainer::Mkv => "video/x-matroska",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConnectRequest {
     pub protocol: String,
     pub url: String,
+    /// ストリーム名（録画ファイル名の{stream_name}に使用）
+    pub name: Option<String>,
+    /// 録画開始前に遡って保持するプリロールの長さ（秒）。未指定の場合は設定値
+    pub preroll_seconds: Option<u64>,
+    /// RTSP認証のユーザー名とパスワード
+    pub username: Option<String>,
+    pub password: Option<Password>,
+    /// RTSPの下位トランスポート（auto / tcp / udp / multicast）
+    pub transport: Option<StreamTransport>,
+    /// rtspsrcのジッタバッファの長さ（ミリ秒）
+    pub latency_ms: Option<u32>,
+    /// 接続のタイムアウト（秒）
+    pub timeout_seconds: Option<u64>,
+    /// 音声トラックを録画・配信するか。未指定の場合は設定値
+    pub audio: Option<bool>,
+    /// HLSを出力するか。未指定の場合は設定値
+    pub hls: Option<bool>,
+}
+
+/// WHIPの配信を受け付ける際のオプション（クエリパラメータ）
+#[derive(Debug, Default, Deserialize)]
+pub struct WhipQuery {
+    /// ストリーム名（録画ファイル名の{stream_name}に使用）
+    pub name: Option<String>,
+    /// 録画開始前に遡って保持するプリロールの長さ（秒）。未指定の場合は設定値
+    pub preroll_seconds: Option<u64>,
+    /// 音声トラックを録画・配信するか。未指定の場合は設定値
+    pub audio: Option<bool>,
+    /// HLSを出力するか。未指定の場合は設定値
+    pub hls: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -47,18 +201,106 @@ pub struct DisconnectResponse {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StreamStatus {
+    pub name: Option<String>,
     pub is_connected: bool,
     pub protocol: Option<String>,
     pub url: Option<String>,
     pub is_recording: bool,
+    /// 録画中の録画ID
+    pub recording_ids: Vec<String>,
+    /// 受信している映像のコーデック（判定前はNone）
+    pub codec: Option<VideoCodec>,
+    /// 受信している音声のコーデック（音声がない、または無効の場合はNone）
+    pub audio_codec: Option<AudioCodec>,
+    /// 最初のキーフレームが届き、録画を開始できる状態か
+    pub ready: bool,
+    /// 最後に映像が届いてからの経過時間（ミリ秒）。未受信の場合はNone
+    pub last_buffer_age_ms: Option<u64>,
+    pub health: StreamHealth,
+    /// 現在の途絶からの再接続の試行回数
+    pub reconnect_attempts: u32,
+    /// ストリームが途絶えた直近の原因
+    pub last_error: Option<String>,
+    /// WebRTC（WHEP）の視聴セッションの数
+    pub webrtc_viewers: usize,
+    /// HLSを出力中か（/api/v1/streams/{stream_id}/hls/playlist.m3u8で視聴できる）
+    pub hls: bool,
     pub connected_at: Option<DateTime<Utc>>,
 }
 
+/// 1秒ごとに集計するストリームの受信統計
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamStatsSample {
+    pub timestamp: DateTime<Utc>,
+    /// 受信した映像のビットレート（bit/s）
+    pub bitrate_bps: u64,
+    pub fps: f64,
+    /// 直近のキーフレームの間隔（ミリ秒）
+    pub keyframe_interval_ms: Option<u64>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// 接続してから受信したRTPパケット数（音声を含む）
+    pub packets_received: u64,
+    /// 接続してからロスしたRTPパケット数（音声を含む）
+    pub packets_lost: u64,
+    /// 前回の集計からのパケットロス率（%）
+    pub packet_loss_percent: f64,
+    /// jitterbufferの平均ジッタ（ミリ秒）
+    pub jitter_ms: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamStatsResponse {
+    pub stream_id: StreamId,
+    pub current: Option<StreamStatsSample>,
+    /// 古い順の直近の統計（スパークライン表示用）
+    pub history: Vec<StreamStatsSample>,
+}
+
+/// ストリームの受信状態
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum StreamHealth {
+    /// 接続後、最初の映像を待っている
+    #[default]
+    Connecting,
+    /// 映像を受信している
+    Live,
+    /// 映像が一定時間届いていない
+    Stalled,
+    /// 受信側を作り直して再接続している
+    Reconnecting,
+    /// 再接続を諦めた（切断して接続し直す必要がある）
+    Failed,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StartRecordingRequest {
+    /// セグメントの最大長（秒）。指定するとセグメント録画になる
+    pub segment_duration_seconds: Option<u64>,
+    /// セグメントの最大サイズ（バイト）。指定するとセグメント録画になる
+    pub segment_max_size_bytes: Option<u64>,
+    /// コンテナ形式（mp4 / fmp4 / mkv）。未指定の場合は設定値
+    pub container: Option<RecordingContainer>,
+    /// ユーザー指定のラベル（ファイル名テンプレートの{label}に展開）
+    pub filename: Option<String>,
+    /// ストリームのプリロールを録画の先頭に含めるか（既定: true）
+    pub preroll: Option<bool>,
+    /// 録画の最大長（秒、一時停止中の時間を除く）。到達すると自動的に停止する
+    pub max_duration_seconds: Option<u64>,
+    /// 録画の最大サイズ（バイト）。到達すると自動的に停止する
+    pub max_size_bytes: Option<u64>,
+    /// 録画を自動的に停止する時刻
+    pub stop_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StartRecordingResponse {
     pub recording_id: String,
     pub stream_id: StreamId,
     pub location: String,
+    /// 録画の先頭に含めた開始前の映像の長さ（ミリ秒）
+    pub preroll_ms: i64,
     pub message: String,
     pub status: String,
 }
@@ -67,6 +309,23 @@ pub struct StartRecordingResponse {
 pub struct StopRecordingResponse {
     pub recording_id: String,
     pub stream_id: StreamId,
+    pub stop_reason: StopReason,
+    pub status: String,
+    pub message: String,
+}
+
+/// ストリームの録画をまとめて停止した結果（録画ごと）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StopStreamRecordingResult {
+    pub recording_id: String,
+    /// RECORDING_STOPPED または STOP_FAILED
+    pub status: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PauseRecordingResponse {
+    pub recording_id: String,
     pub status: String,
     pub message: String,
 }
@@ -80,6 +339,18 @@ pub struct RecordingListItem {
     pub duration: Option<i64>,
     pub file_size: Option<i64>,
     pub stream_id: Option<StreamId>,
+    pub codec: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecordingListQuery {
+    pub stream_id: Option<StreamId>,
+}
+
+/// イベントの購読条件（stream_idを指定するとそのストリームのイベントのみ）
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventQuery {
+    pub stream_id: Option<StreamId>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -92,6 +363,324 @@ pub struct RecordingDetails {
     pub duration: Option<i64>,
     pub file_size: Option<i64>,
     pub stream_id: Option<StreamId>,
+    pub protocol: Option<String>,
+    pub source_url: Option<String>,
+    pub codec: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub framerate: Option<f64>,
+    pub audio_codec: Option<String>,
+    /// クリップの書き出し元の録画（ストリームから録画した場合はNone）
+    pub parent_recording_id: Option<Uuid>,
+    pub segment_duration_seconds: Option<i64>,
+    pub segment_max_size_bytes: Option<i64>,
+    pub container: RecordingContainer,
+    /// start_timeより前に含まれる映像の長さ（ミリ秒）
+    pub preroll_ms: i64,
+    pub max_duration_seconds: Option<i64>,
+    pub max_size_bytes: Option<i64>,
+    pub stop_at: Option<DateTime<Utc>>,
+    pub stop_reason: Option<StopReason>,
+    pub is_paused: bool,
+    /// 録画時間のうち実際に記録した時間（秒）。録画中の場合は現在時刻まで
+    pub active_seconds: i64,
+    /// 一時停止していた時間（秒）
+    pub paused_seconds: i64,
+    pub pauses: Vec<RecordingPause>,
+}
+
+impl RecordingDetails {
+    /// 一時停止区間から記録時間と一時停止時間を計算して設定する
+    pub fn with_pauses(mut self, pauses: Vec<RecordingPause>) -> Self {
+        let end_time = self.end_time.unwrap_or_else(Utc::now);
+        self.paused_seconds = pauses
+            .iter()
+            .map(|pause| {
+                (pause.resumed_at.unwrap_or(end_time) - pause.paused_at)
+                    .num_seconds()
+                    .max(0)
+            })
+            .sum();
+        let total_seconds = (end_time - self.start_time).num_seconds().max(0);
+        self.active_seconds = (total_seconds - self.paused_seconds).max(0);
+        self.is_paused = self.end_time.is_none() && pauses.iter().any(|p| p.resumed_at.is_none());
+        self.pauses = pauses;
+        self
+    }
+}
+
+/// RTSPの下位トランスポート
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "stream_transport", rename_all = "UPPERCASE")]
+#[serde(rename_all = "lowercase")]
+pub enum StreamTransport {
+    /// rtspsrcの既定（UDP、失敗時はTCP）
+    #[default]
+    Auto,
+    Tcp,
+    Udp,
+    Multicast,
+}
+
+/// 保存済みのストリーム接続設定
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct StreamProfile {
+    pub id: Uuid,
+    pub name: String,
+    pub protocol: String,
+    pub url: String,
+    pub username: Option<String>,
+    /// 暗号化済みのパスワード。APIのレスポンスとDebug出力には含めない
+    #[serde(skip)]
+    pub password_encrypted: Option<Password>,
+    pub latency_ms: i32,
+    pub transport: StreamTransport,
+    /// 接続のタイムアウト（秒）。未指定の場合は既定値
+    pub timeout_seconds: Option<i64>,
+    /// 音声トラックを録画・配信するか。未指定の場合は設定値
+    pub audio: Option<bool>,
+    /// 起動時に自動的に接続するか（stream_idはプロファイルID）
+    pub auto_connect: bool,
+    /// プリロールの長さ（秒）。未指定の場合は設定値
+    pub preroll_seconds: Option<i64>,
+    /// 録画の既定のコンテナ形式
+    pub container: Option<RecordingContainer>,
+    /// 録画の既定のセグメント長（秒）
+    pub segment_duration_seconds: Option<i64>,
+    /// 録画の既定のセグメント最大サイズ（バイト）
+    pub segment_max_size_bytes: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamProfileRequest {
+    pub name: String,
+    pub protocol: String,
+    pub url: String,
+    pub username: Option<String>,
+    /// 更新時に未指定の場合は既存の値を維持し、空文字列の場合は削除する
+    pub password: Option<Password>,
+    pub latency_ms: Option<u32>,
+    pub transport: Option<StreamTransport>,
+    pub timeout_seconds: Option<u64>,
+    pub audio: Option<bool>,
+    pub auto_connect: Option<bool>,
+    pub preroll_seconds: Option<u64>,
+    pub container: Option<RecordingContainer>,
+    pub segment_duration_seconds: Option<u64>,
+    pub segment_max_size_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "schedule_kind", rename_all = "UPPERCASE")]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleKind {
+    /// 曜日と開始・終了時刻で指定する週次の録画枠
+    Weekly,
+    /// cron式の発火時刻からduration_seconds秒の録画枠
+    Cron,
+}
+
+/// ストリームプロファイルに紐づく録画スケジュール
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RecordingSchedule {
+    pub id: Uuid,
+    pub profile_id: Uuid,
+    pub name: String,
+    pub kind: ScheduleKind,
+    /// ISO曜日（1=月曜 .. 7=日曜）。WEEKLYのみ
+    pub weekdays: Vec<i16>,
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+    pub cron_expression: Option<String>,
+    pub duration_seconds: Option<i64>,
+    /// IANAタイムゾーン名（例: Asia/Tokyo）
+    pub timezone: String,
+    /// ファイル名テンプレートの{label}に展開する値
+    pub label: Option<String>,
+    pub enabled: bool,
+    /// 再起動時に進行中だった録画枠を残り時間だけ録画するか
+    pub catch_up: bool,
+    /// スケジューラが最後に処理した録画枠の開始時刻
+    pub last_window_start: Option<DateTime<Utc>>,
+    /// last_window_startの録画枠でスケジューラが開始した録画
+    pub last_recording_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScheduleRequest {
+    pub profile_id: Uuid,
+    pub name: String,
+    pub kind: ScheduleKind,
+    #[serde(default)]
+    pub weekdays: Vec<i16>,
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+    pub cron_expression: Option<String>,
+    pub duration_seconds: Option<i64>,
+    pub timezone: Option<String>,
+    pub label: Option<String>,
+    pub enabled: Option<bool>,
+    pub catch_up: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleListQuery {
+    pub profile_id: Option<Uuid>,
+}
+
+/// Webhookで通知するイベントの種類
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WebhookEventType {
+    /// 録画が停止し、ファイルが確定した
+    #[serde(rename = "recording.completed")]
+    RecordingCompleted,
+    /// 録画の開始または停止（ファイルの確定）に失敗した
+    #[serde(rename = "recording.failed")]
+    RecordingFailed,
+    /// ストリームに接続した（再接続を含む）
+    #[serde(rename = "stream.connected")]
+    StreamConnected,
+    /// ストリームが途絶えた、または再接続を諦めた
+    #[serde(rename = "stream.lost")]
+    StreamLost,
+    /// 送信テスト用（購読の有無にかかわらず指定したWebhookにのみ送信する）
+    #[serde(rename = "ping")]
+    Ping,
+}
+
+impl WebhookEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::RecordingCompleted => "recording.completed",
+            Self::RecordingFailed => "recording.failed",
+            Self::StreamConnected => "stream.connected",
+            Self::StreamLost => "stream.lost",
+            Self::Ping => "ping",
+        }
+    }
+}
+
+/// イベントを通知するWebhookの購読
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub name: String,
+    pub url: String,
+    /// 暗号化済みの署名用シークレット。APIのレスポンスには含めない
+    #[serde(skip)]
+    pub secret_encrypted: Option<String>,
+    pub event_types: Vec<String>,
+    /// 指定した場合はこのストリームのイベントのみ通知する
+    pub stream_id: Option<StreamId>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookRequest {
+    pub name: String,
+    pub url: String,
+    /// 更新時に未指定の場合は既存の値を維持し、空文字列の場合は削除する（署名なしで送信する）
+    pub secret: Option<Password>,
+    pub event_types: Vec<WebhookEventType>,
+    pub stream_id: Option<StreamId>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "webhook_delivery_status", rename_all = "UPPERCASE")]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookDeliveryStatus {
+    /// 送信待ち、または再送待ち
+    Pending,
+    Succeeded,
+    /// 最大試行回数に達した、またはWebhookが無効化された
+    Failed,
+}
+
+/// Webhookの配信履歴（イベントとWebhookごとに1件）
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event_type: String,
+    /// 送信するJSON
+    pub payload: serde_json::Value,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: i32,
+    /// 最後の試行のHTTPステータス
+    pub response_status: Option<i32>,
+    /// 最後の試行のエラー
+    pub last_error: Option<String>,
+    /// 次の試行の予定時刻（PENDINGのみ）
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookDeliveryQuery {
+    pub status: Option<WebhookDeliveryStatus>,
+    /// 新しい順に返す件数（既定100、最大1000）
+    pub limit: Option<i64>,
+}
+
+/// クリップの書き出し方法
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "clip_export_mode", rename_all = "UPPERCASE")]
+#[serde(rename_all = "lowercase")]
+pub enum ClipExportMode {
+    /// ストリームコピー（開始位置の直前のキーフレームから書き出す）
+    #[default]
+    Copy,
+    /// H.264 / AACに再エンコードし、指定した位置からフレーム単位で書き出す
+    Reencode,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "clip_export_status", rename_all = "UPPERCASE")]
+#[serde(rename_all = "lowercase")]
+pub enum ClipExportStatus {
+    /// 同時実行数の上限により実行待ち
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// 録画からクリップを書き出すジョブ
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ClipExport {
+    pub id: Uuid,
+    /// 書き出し元の録画
+    pub recording_id: Uuid,
+    /// 書き出したクリップの録画（完了後のみ）
+    pub clip_recording_id: Option<Uuid>,
+    /// 書き出し元のファイルの先頭（プリロールを含む）からの位置（ミリ秒）
+    pub start_offset_ms: i64,
+    pub end_offset_ms: i64,
+    pub mode: ClipExportMode,
+    pub status: ClipExportStatus,
+    /// 進捗（0.0〜1.0）
+    pub progress: f64,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// クリップの書き出しリクエスト。位置は録画ファイルの先頭（プリロールを含む）からの秒数
+#[derive(Debug, Deserialize)]
+pub struct ClipExportRequest {
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    #[serde(default)]
+    pub mode: ClipExportMode,
 }
 
 #[derive(Debug, Serialize)]
@@ -100,12 +689,19 @@ pub struct DebugStatus {
     pub is_recording: bool,
     pub protocol: Option<String>,
     pub url: Option<String>,
+    /// 最初のキーフレームがteeに届き、録画を開始できる状態か
     pub tee_ready: bool,
+    /// 最後に映像が届いてからの経過時間（ミリ秒）
+    pub last_buffer_age_ms: Option<u64>,
+    /// 直近の受信統計
+    pub stats: Option<StreamStatsSample>,
     pub pipeline_state: Option<String>,
     pub pipeline_pending_state: Option<String>,
     pub tee_state: Option<String>,
     pub tee_pending_state: Option<String>,
     pub active_recording_pads: usize,
+    /// WebRTC（WHEP）の視聴セッションのID
+    pub webrtc_sessions: Vec<String>,
 }
 
 impl From<Recording> for RecordingListItem {
@@ -117,7 +713,8 @@ impl From<Recording> for RecordingListItem {
             end_time: recording.end_time,
             duration: recording.duration_seconds,
             file_size: recording.file_size_bytes,
-            stream_id: None,
+            stream_id: recording.stream_id,
+            codec: recording.codec,
         }
     }
 }
@@ -132,7 +729,27 @@ impl From<Recording> for RecordingDetails {
             end_time: recording.end_time,
             duration: recording.duration_seconds,
             file_size: recording.file_size_bytes,
-            stream_id: None,
+            stream_id: recording.stream_id,
+            protocol: recording.protocol,
+            source_url: recording.source_url,
+            codec: recording.codec,
+            width: recording.width,
+            height: recording.height,
+            framerate: recording.framerate,
+            audio_codec: recording.audio_codec,
+            parent_recording_id: recording.parent_recording_id,
+            segment_duration_seconds: recording.segment_duration_seconds,
+            segment_max_size_bytes: recording.segment_max_size_bytes,
+            container: recording.container,
+            preroll_ms: recording.preroll_ms,
+            max_duration_seconds: recording.max_duration_seconds,
+            max_size_bytes: recording.max_size_bytes,
+            stop_at: recording.stop_at,
+            stop_reason: recording.stop_reason,
+            is_paused: false,
+            active_seconds: recording.duration_seconds.unwrap_or(0),
+            paused_seconds: 0,
+            pauses: Vec::new(),
         }
     }
 }
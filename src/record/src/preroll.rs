@@ -0,0 +1,125 @@
+use crate::error::RecordError;
+use gstreamer::prelude::*;
+use gstreamer::{Buffer, BufferFlags, Caps, ClockTime, Pad, Segment};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// プリロールの長さの上限（秒）。GOP単位でメモリに保持するため制限する
+pub const MAX_PREROLL_SECONDS: u64 = 60;
+
+/// プリロールの長さが上限以内か確認する
+pub fn validate_preroll_seconds(seconds: u64) -> Result<(), RecordError> {
+    if seconds > MAX_PREROLL_SECONDS {
+        return Err(RecordError::StreamError(format!(
+            "preroll_seconds must be at most {}",
+            MAX_PREROLL_SECONDS
+        )));
+    }
+    Ok(())
+}
+
+/// キーフレームから始まる1GOP分のバッファ
+#[derive(Debug)]
+struct Gop {
+    start: ClockTime,
+    buffers: Vec<Buffer>,
+}
+
+#[derive(Debug, Default)]
+struct PrerollInner {
+    gops: VecDeque<Gop>,
+}
+
+/// 録画開始前のN秒分の符号化済み映像を保持するリングバッファ。
+/// GOP単位で保持するため、取り出した内容は必ずキーフレームから始まる。
+#[derive(Debug, Clone)]
+pub struct PrerollBuffer {
+    duration: ClockTime,
+    inner: Arc<Mutex<PrerollInner>>,
+}
+
+/// 録画開始時に取り出したプリロールの内容
+#[derive(Debug)]
+pub struct PrerollSnapshot {
+    pub buffers: Vec<Buffer>,
+    pub caps: Caps,
+    pub segment: Segment,
+}
+
+impl PrerollSnapshot {
+    /// 先頭のキーフレームから最後のバッファまでの長さ
+    pub fn duration(&self) -> ClockTime {
+        let first = self.buffers.first().and_then(|buffer| buffer_time(buffer));
+        let last = self.buffers.last().and_then(|buffer| buffer_time(buffer));
+        match (first, last) {
+            (Some(first), Some(last)) => last.saturating_sub(first),
+            _ => ClockTime::ZERO,
+        }
+    }
+
+    /// 最後のバッファのタイムスタンプ（ライブ側の重複バッファを除外する基準）
+    pub fn last_time(&self) -> Option<ClockTime> {
+        self.buffers.last().and_then(|buffer| buffer_time(buffer))
+    }
+}
+
+impl PrerollBuffer {
+    pub fn new(duration: ClockTime) -> Self {
+        Self {
+            duration,
+            inner: Arc::new(Mutex::new(PrerollInner::default())),
+        }
+    }
+
+    /// バッファを追加し、保持期間を超えた古いGOPを破棄する
+    pub fn push(&self, buffer: &Buffer) {
+        let Some(time) = buffer_time(buffer) else {
+            return;
+        };
+        let mut inner = self.inner.lock().unwrap();
+        if !buffer.flags().contains(BufferFlags::DELTA_UNIT) {
+            inner.gops.push_back(Gop {
+                start: time,
+                buffers: Vec::new(),
+            });
+        }
+        // 最初のキーフレームが来るまでは保持しない
+        let Some(gop) = inner.gops.back_mut() else {
+            return;
+        };
+        gop.buffers.push(buffer.clone());
+
+        // 2番目のGOPの開始が保持期間より前であれば、最も古いGOPは不要
+        while inner.gops.len() > 1 && inner.gops[1].start + self.duration <= time {
+            inner.gops.pop_front();
+        }
+    }
+
+    /// 現在の内容を取り出す。teeのsinkパッドからcaps/segmentを取得できない場合はNone
+    pub fn snapshot(&self, pad: &Pad) -> Option<PrerollSnapshot> {
+        let caps = pad.current_caps()?;
+        let segment = pad
+            .sticky_event::<gstreamer::event::Segment>(0)?
+            .segment()
+            .clone();
+        let inner = self.inner.lock().unwrap();
+        let buffers: Vec<Buffer> = inner
+            .gops
+            .iter()
+            .flat_map(|gop| gop.buffers.iter().cloned())
+            .collect();
+        if buffers.is_empty() {
+            return None;
+        }
+        Some(PrerollSnapshot {
+            buffers,
+            caps,
+            segment,
+        })
+    }
+}
+
+/// デコード順で単調増加するタイムスタンプ（DTS、なければPTS）
+pub fn buffer_time(buffer: &gstreamer::BufferRef) -> Option<ClockTime> {
+    buffer.dts_or_pts()
+}
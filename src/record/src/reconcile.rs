@@ -0,0 +1,368 @@
+use crate::clips::link_stream;
+use crate::config::Config;
+use crate::database::Database;
+use crate::error::RecordError;
+use crate::models::{ClipExportMode, Recording, RecordingContainer, RecordingStatus, StopReason};
+use crate::recording::{build_muxer, file_size};
+use gstreamer::prelude::*;
+use gstreamer::{ClockTime, ElementFactory, MessageView, Pipeline, State};
+use gstreamer_pbutils::Discoverer;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+/// ファイル検査（Discoverer）のタイムアウト
+const PROBE_TIMEOUT_SECONDS: u64 = 30;
+/// 再muxによる修復のタイムアウト
+const REPAIR_TIMEOUT_SECONDS: u64 = 600;
+
+/// 起動時の録画整合性チェックの結果
+#[derive(Debug, Default)]
+pub struct ReconcileReport {
+    /// 再生可能なファイルが残っていたためCOMPLETEDにした録画数
+    pub completed: usize,
+    /// FAILEDにした録画数
+    pub failed: usize,
+    /// recording_directory内にあるがDBに登録されていないファイル
+    pub untracked_files: Vec<PathBuf>,
+}
+
+/// 録画ファイルの検査結果
+enum FileCheck {
+    /// 再生可能（修復できたものを含む）
+    Playable {
+        duration_seconds: i64,
+        file_size: i64,
+    },
+    /// 再生不可
+    Unplayable { file_size: i64 },
+}
+
+/// 前回の異常終了でRECORDINGのまま残った録画を整理し、DBに存在しないファイルを報告する
+pub async fn reconcile_recordings(
+    config: &Config,
+    database: &Database,
+) -> Result<ReconcileReport, RecordError> {
+    let mut report = ReconcileReport::default();
+
+    let orphaned = database
+        .list_recordings_by_status(RecordingStatus::Recording)
+        .await?;
+    if !orphaned.is_empty() {
+        info!(
+            "Reconciling {} recordings left in RECORDING state",
+            orphaned.len()
+        );
+    }
+    for recording in orphaned {
+        let result = if recording.is_segmented() {
+            reconcile_segmented(database, &recording).await
+        } else {
+            reconcile_single(database, &recording).await
+        };
+        match result {
+            Ok(RecordingStatus::Completed) => report.completed += 1,
+            Ok(_) => report.failed += 1,
+            Err(e) => error!(
+                "[recording {}] Failed to reconcile recording: {}",
+                recording.id, e
+            ),
+        }
+    }
+
+    report.untracked_files = find_untracked_files(config, database).await?;
+    for path in &report.untracked_files {
+        warn!(
+            "Recording file has no database row: {}",
+            path.to_string_lossy()
+        );
+    }
+
+    info!(
+        "Recording reconciliation finished: completed={}, failed={}, untracked_files={}",
+        report.completed,
+        report.failed,
+        report.untracked_files.len()
+    );
+    Ok(report)
+}
+
+/// 単一ファイル録画を検査し、COMPLETEDまたはFAILEDにする
+async fn reconcile_single(
+    database: &Database,
+    recording: &Recording,
+) -> Result<RecordingStatus, RecordError> {
+    match check_file_blocking(PathBuf::from(&recording.file_path), recording.container).await? {
+        FileCheck::Playable {
+            duration_seconds,
+            file_size,
+        } => {
+            let end_time = recording.start_time + chrono::Duration::seconds(duration_seconds);
+            database
+                .update_recording_completed(
+                    recording.id,
+                    end_time,
+                    duration_seconds,
+                    file_size,
+                    Some(StopReason::ServiceRestart),
+                )
+                .await?;
+            info!(
+                "[recording {}] Recovered orphaned recording: duration={}s, size={} bytes",
+                recording.id, duration_seconds, file_size
+            );
+            Ok(RecordingStatus::Completed)
+        }
+        FileCheck::Unplayable { file_size } => {
+            database
+                .update_recording_failed(
+                    recording.id,
+                    None,
+                    None,
+                    Some(file_size),
+                    Some(StopReason::ServiceRestart),
+                )
+                .await?;
+            warn!(
+                "[recording {}] Orphaned recording is not playable, marked as FAILED (path={})",
+                recording.id, recording.file_path
+            );
+            Ok(RecordingStatus::Failed)
+        }
+    }
+}
+
+/// セグメント録画の未完了セグメントを検査し、再生可能なセグメントがあればCOMPLETEDにする
+async fn reconcile_segmented(
+    database: &Database,
+    recording: &Recording,
+) -> Result<RecordingStatus, RecordError> {
+    let mut duration_seconds = 0;
+    let mut playable_segments = 0;
+    for segment in database.list_segments(recording.id).await? {
+        if segment.end_time.is_some() {
+            duration_seconds += segment.duration_seconds.unwrap_or(0);
+            playable_segments += 1;
+            continue;
+        }
+        match check_file_blocking(PathBuf::from(&segment.file_path), recording.container).await? {
+            FileCheck::Playable {
+                duration_seconds: segment_duration,
+                file_size,
+            } => {
+                let end_time = segment.start_time + chrono::Duration::seconds(segment_duration);
+                database
+                    .update_segment_completed(
+                        recording.id,
+                        segment.segment_index,
+                        end_time,
+                        file_size,
+                    )
+                    .await?;
+                duration_seconds += segment_duration;
+                playable_segments += 1;
+            }
+            // 再生できないセグメントは長さが不明のため、end_timeを空のまま残す
+            FileCheck::Unplayable { .. } => warn!(
+                "[recording {}] Segment {} is not playable (path={})",
+                recording.id, segment.segment_index, segment.file_path
+            ),
+        }
+    }
+
+    let total_size = file_size(Path::new(&recording.file_path));
+    if playable_segments == 0 {
+        database
+            .update_recording_failed(
+                recording.id,
+                None,
+                None,
+                Some(total_size),
+                Some(StopReason::ServiceRestart),
+            )
+            .await?;
+        warn!(
+            "[recording {}] Orphaned segmented recording has no playable segments, marked as FAILED",
+            recording.id
+        );
+        return Ok(RecordingStatus::Failed);
+    }
+
+    let end_time = recording.start_time + chrono::Duration::seconds(duration_seconds);
+    database
+        .update_recording_completed(
+            recording.id,
+            end_time,
+            duration_seconds,
+            total_size,
+            Some(StopReason::ServiceRestart),
+        )
+        .await?;
+    info!(
+        "[recording {}] Recovered orphaned segmented recording: segments={}, duration={}s, size={} bytes",
+        recording.id, playable_segments, duration_seconds, total_size
+    );
+    Ok(RecordingStatus::Completed)
+}
+
+async fn check_file_blocking(
+    path: PathBuf,
+    container: RecordingContainer,
+) -> Result<FileCheck, RecordError> {
+    tokio::task::spawn_blocking(move || check_file(&path, container))
+        .await
+        .map_err(|e| RecordError::InternalError(format!("File check task failed: {}", e)))
+}
+
+/// ファイルが再生可能か検査し、可能であれば長さを返す。
+/// FMP4/MKVは書き込み途中でも読めるため、長さが取れない場合は再muxで修復を試みる。
+/// 通常のMP4はmoovが書かれていないと修復できない。
+fn check_file(path: &Path, container: RecordingContainer) -> FileCheck {
+    let size = file_size(path);
+    if size == 0 {
+        return FileCheck::Unplayable { file_size: size };
+    }
+    if let Some(duration_seconds) = probe_duration(path) {
+        return FileCheck::Playable {
+            duration_seconds,
+            file_size: size,
+        };
+    }
+    if container != RecordingContainer::Mp4 {
+        match repair_file(path, container) {
+            Ok(()) => {
+                if let Some(duration_seconds) = probe_duration(path) {
+                    info!("Repaired recording file: {}", path.to_string_lossy());
+                    return FileCheck::Playable {
+                        duration_seconds,
+                        file_size: file_size(path),
+                    };
+                }
+            }
+            Err(e) => warn!(
+                "Failed to repair recording file {}: {}",
+                path.to_string_lossy(),
+                e
+            ),
+        }
+    }
+    FileCheck::Unplayable { file_size: size }
+}
+
+/// Discovererでファイルの長さ（秒）を取得する
+pub fn probe_duration(path: &Path) -> Option<i64> {
+    let uri = glib::filename_to_uri(path, None).ok()?;
+    let discoverer = Discoverer::new(ClockTime::from_seconds(PROBE_TIMEOUT_SECONDS)).ok()?;
+    let info = discoverer.discover_uri(&uri).ok()?;
+    info.duration()
+        .filter(|duration| !duration.is_zero())
+        .map(|duration| duration.seconds() as i64)
+}
+
+/// ストリームコピーで同じコンテナ形式に再muxし、インデックスと長さを書き直す
+fn repair_file(path: &Path, container: RecordingContainer) -> Result<(), RecordError> {
+    let repaired = path.with_extension(format!("repair.{}", container.extension()));
+    let pipeline = Pipeline::new();
+    let filesrc = ElementFactory::make("filesrc")
+        .property("location", path.to_string_lossy().to_string())
+        .build()?;
+    let parsebin = ElementFactory::make("parsebin").build()?;
+    // 録画と同じmuxerを使い、DBのcontainerとファイルの形式（fMP4など）を一致させる
+    let muxer = build_muxer(container)?;
+    let filesink = ElementFactory::make("filesink")
+        .property("location", repaired.to_string_lossy().to_string())
+        .build()?;
+    pipeline.add_many([&filesrc, &parsebin, &muxer, &filesink])?;
+    filesrc.link(&parsebin)?;
+    muxer.link(&filesink)?;
+
+    // 映像と音声のストリームごとにmuxerのパッドを要求してつなぐ
+    let pipeline_weak = pipeline.downgrade();
+    let muxer_weak = muxer.downgrade();
+    parsebin.connect_pad_added(move |_, src_pad| {
+        let (Some(pipeline), Some(muxer)) = (pipeline_weak.upgrade(), muxer_weak.upgrade()) else {
+            return;
+        };
+        if let Err(e) = link_stream(&pipeline, &muxer, src_pad, ClipExportMode::Copy) {
+            warn!("Failed to link repair stream {}: {}", src_pad.name(), e);
+        }
+    });
+
+    pipeline.set_state(State::Playing)?;
+    let bus = pipeline
+        .bus()
+        .ok_or_else(|| RecordError::InternalError("Failed to get bus from pipeline".into()))?;
+    let result = match bus.timed_pop_filtered(
+        ClockTime::from_seconds(REPAIR_TIMEOUT_SECONDS),
+        &[gstreamer::MessageType::Eos, gstreamer::MessageType::Error],
+    ) {
+        Some(msg) => match msg.view() {
+            MessageView::Eos(..) => Ok(()),
+            MessageView::Error(err) => Err(RecordError::StreamError(err.error().to_string())),
+            _ => Ok(()),
+        },
+        None => Err(RecordError::StreamError(
+            "Timeout while repairing recording file".into(),
+        )),
+    };
+    pipeline.set_state(State::Null)?;
+
+    match result {
+        Ok(()) => {
+            std::fs::rename(&repaired, path)?;
+            Ok(())
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&repaired);
+            Err(e)
+        }
+    }
+}
+
+/// recording_directory内でDBに登録されていないファイルを探す
+async fn find_untracked_files(
+    config: &Config,
+    database: &Database,
+) -> Result<Vec<PathBuf>, RecordError> {
+    let known: HashSet<PathBuf> = database
+        .list_recording_file_paths()
+        .await?
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+    let root = config.recording_directory.clone();
+    let files = tokio::task::spawn_blocking(move || {
+        let mut files = Vec::new();
+        collect_files(&root, &mut files);
+        files
+    })
+    .await
+    .map_err(|e| RecordError::InternalError(format!("Directory scan task failed: {}", e)))?;
+
+    Ok(files
+        .into_iter()
+        .filter(|path| !known.contains(path))
+        .collect())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!(
+                "Failed to read recording directory {}: {}",
+                dir.to_string_lossy(),
+                e
+            );
+            return;
+        }
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
@@ -1,21 +1,281 @@
+use crate::codec::VideoCodec;
+use crate::database::Database;
 use crate::error::RecordError;
+use crate::events::{EventBus, EventKind};
+use crate::models::{Recording, RecordingContainer, StopReason};
+use crate::preroll::{buffer_time, PrerollBuffer, MAX_PREROLL_SECONDS};
 use crate::stream::{StreamId, StreamState};
+use chrono::{DateTime, Local, Utc};
 use glib::prelude::ObjectExt;
 use gstreamer::prelude::*;
-use gstreamer::{Bin, Element, ElementFactory, State};
+use gstreamer::{Bin, ClockTime, Element, ElementFactory, PadProbeReturn, PadProbeType, State};
+use gstreamer_app::AppSrc;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::{Mutex};
-use tracing::{error, info};
+use tokio::sync::{mpsc, Mutex};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
-/// 録画開始ロジック
+/// セグメント録画の分割条件
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SegmentOptions {
+    pub max_duration_seconds: Option<u64>,
+    pub max_size_bytes: Option<u64>,
+}
+
+/// 録画Bin構築時のオプション
+#[derive(Debug, Clone, Default)]
+pub struct RecordingOptions {
+    /// 録画ファイルのパス（セグメント録画の場合はセグメントを格納するディレクトリ）
+    pub location: String,
+    pub segment: Option<SegmentOptions>,
+    pub container: RecordingContainer,
+    /// ストリームのプリロールを録画の先頭に含めるか
+    pub preroll: bool,
+    pub limits: RecordingLimits,
+}
+
+/// 録画を自動的に停止する条件
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecordingLimits {
+    pub max_duration: Option<std::time::Duration>,
+    pub max_size_bytes: Option<u64>,
+    pub stop_at: Option<DateTime<Utc>>,
+}
+
+impl RecordingLimits {
+    /// 停止条件に到達していればその理由を返す
+    pub fn reached(
+        &self,
+        elapsed: std::time::Duration,
+        size_bytes: u64,
+        now: DateTime<Utc>,
+    ) -> Option<StopReason> {
+        if self.stop_at.is_some_and(|stop_at| now >= stop_at) {
+            Some(StopReason::Schedule)
+        } else if self.max_duration.is_some_and(|max| elapsed >= max) {
+            Some(StopReason::DurationLimit)
+        } else if self.max_size_bytes.is_some_and(|max| size_bytes >= max) {
+            Some(StopReason::SizeLimit)
+        } else {
+            None
+        }
+    }
+}
+
+/// 録画Bin内で最終的にファイルへ書き込むsink要素の名前（停止時のEOS待機に使用）
+pub const RECORDING_SINK_NAME: &str = "rec-sink";
+
+/// セグメント録画のsplitmuxsinkの名前（再接続時のセグメント分割に使用）
+pub const RECORDING_SPLITMUX_NAME: &str = "rec-splitmux";
+
+/// 録画Binのqueueの上限（max-size-buffers、max-size-bytes、max-size-time）
+#[derive(Debug, Clone, Copy)]
+struct QueueLimits {
+    buffers: u32,
+    bytes: u32,
+    time: u64,
+}
+
+impl QueueLimits {
+    /// 録画中の映像のqueue
+    const LIVE: QueueLimits = QueueLimits {
+        buffers: 100,
+        bytes: 0,
+        time: 0,
+    };
+
+    /// プリロールを書き出す間にライブ映像を溜めるqueue（プリロールの最大長まで）
+    const PREROLL: QueueLimits = QueueLimits {
+        buffers: 0,
+        bytes: 0,
+        time: MAX_PREROLL_SECONDS * 1_000_000_000,
+    };
+
+    fn of(queue: &Element) -> Self {
+        QueueLimits {
+            buffers: queue.property("max-size-buffers"),
+            bytes: queue.property("max-size-bytes"),
+            time: queue.property("max-size-time"),
+        }
+    }
+
+    fn apply(self, queue: &Element) {
+        queue.set_property("max-size-buffers", self.buffers);
+        queue.set_property("max-size-bytes", self.bytes);
+        queue.set_property("max-size-time", self.time);
+    }
+}
+
+/// 録画ファイル名テンプレートに埋め込む値
+#[derive(Debug, Clone)]
+pub struct FileNameContext<'a> {
+    pub stream_id: &'a str,
+    pub stream_name: Option<&'a str>,
+    pub recording_id: &'a str,
+    pub label: Option<&'a str>,
+    pub start_time: DateTime<Local>,
+}
+
+/// ファイル名テンプレートを展開し、recording_directoryからの相対パス（拡張子なし）を返す。
+/// 埋め込む値に含まれるパス区切りや記号は`_`に置き換え、空になったディレクトリ階層は省略する。
+pub fn render_file_stem(
+    template: &str,
+    context: &FileNameContext<'_>,
+) -> Result<PathBuf, RecordError> {
+    let mut path = PathBuf::new();
+    for component in template.split('/') {
+        let rendered = clean_component(&render_component(component, context)?);
+        if !rendered.is_empty() {
+            path.push(rendered);
+        }
+    }
+    if path.as_os_str().is_empty() {
+        return Err(RecordError::ConfigError(format!(
+            "Filename template '{}' produced an empty path",
+            template
+        )));
+    }
+    Ok(path)
+}
+
+fn render_component(component: &str, context: &FileNameContext<'_>) -> Result<String, RecordError> {
+    let mut rendered = String::new();
+    let mut rest = component;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| {
+                RecordError::ConfigError(format!(
+                    "Unclosed placeholder in filename template: {}",
+                    component
+                ))
+            })?;
+        let value = match &rest[start + 1..end] {
+            "stream_name" => context.stream_name.unwrap_or(context.stream_id).to_string(),
+            "stream_id" => context.stream_id.to_string(),
+            "recording_id" => context.recording_id.to_string(),
+            "label" => context.label.unwrap_or_default().to_string(),
+            "date" => context.start_time.format("%Y%m%d").to_string(),
+            "time" => context.start_time.format("%H%M%S").to_string(),
+            "datetime" => context.start_time.format("%Y%m%dT%H%M%S").to_string(),
+            "yyyy" => context.start_time.format("%Y").to_string(),
+            "mm" => context.start_time.format("%m").to_string(),
+            "dd" => context.start_time.format("%d").to_string(),
+            placeholder => {
+                return Err(RecordError::ConfigError(format!(
+                    "Unknown placeholder in filename template: {{{}}}",
+                    placeholder
+                )))
+            }
+        };
+        rendered.extend(value.chars().map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        }));
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// 空のプレースホルダで生じた連続する`_`をまとめ、先頭・末尾の区切り文字を取り除く
+/// （`.`も取り除くため`..`による上位ディレクトリ参照は生じない）
+fn clean_component(component: &str) -> String {
+    let mut cleaned = String::with_capacity(component.len());
+    for c in component.chars() {
+        if c == '_' && cleaned.ends_with('_') {
+            continue;
+        }
+        cleaned.push(c);
+    }
+    cleaned
+        .trim_matches(|c| matches!(c, '_' | '-' | '.'))
+        .to_string()
+}
+
+/// splitmuxsinkが新しいセグメントを開いたことを通知する
+#[derive(Debug)]
+struct SegmentOpened {
+    segment_index: i32,
+    file_name: String,
+    file_path: String,
+    start_time: DateTime<Utc>,
+}
+
+/// 録画Binに接続しているteeのsrcパッド
+#[derive(Debug, Clone)]
+pub struct RecordingPads {
+    pub video: gstreamer::Pad,
+    /// 音声を録画している場合のみ
+    pub audio: Option<gstreamer::Pad>,
+}
+
+impl RecordingPads {
+    pub fn iter(&self) -> impl Iterator<Item = &gstreamer::Pad> {
+        std::iter::once(&self.video).chain(self.audio.iter())
+    }
+}
+
+/// 録画開始時にネゴシエーション済みのcapsから取得した映像の形式
+#[derive(Debug, Clone, Default)]
+pub struct MediaInfo {
+    /// コーデック名（例: h264）
+    pub codec: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// フレームレート（可変フレームレートの場合はNone）
+    pub framerate: Option<f64>,
+    /// 音声のコーデック名（音声なしの場合はNone）
+    pub audio_codec: Option<String>,
+}
+
+impl MediaInfo {
+    pub fn from_caps(caps: &gstreamer::CapsRef) -> Self {
+        let Some(structure) = caps.structure(0) else {
+            return Self::default();
+        };
+        let codec = VideoCodec::from_caps(caps)
+            .map(|codec| codec.name().to_string())
+            .unwrap_or_else(|| structure.name().to_string());
+        Self {
+            codec: Some(codec),
+            width: structure.get::<i32>("width").ok(),
+            height: structure.get::<i32>("height").ok(),
+            framerate: structure
+                .get::<gstreamer::Fraction>("framerate")
+                .ok()
+                .filter(|fraction| fraction.numer() > 0 && fraction.denom() > 0)
+                .map(|fraction| fraction.numer() as f64 / fraction.denom() as f64),
+            audio_codec: None,
+        }
+    }
+}
+
+/// 録画開始の結果
+#[derive(Debug, Clone)]
+pub struct RecordingStarted {
+    /// 録画の先頭に含めたプリロールの長さ
+    pub preroll: ClockTime,
+    pub media: MediaInfo,
+}
+
+/// 録画開始ロジック。録画の先頭に含めたプリロールの長さと映像の形式を返す
 pub async fn start_recording_impl(
     streams: Arc<Mutex<HashMap<StreamId, StreamState>>>,
     stream_id: &StreamId,
     recording_id: Uuid,
-    recording_pads: &Arc<Mutex<HashMap<String, gstreamer::Pad>>>, // 追加
-) -> Result<(), RecordError> {
+    options: &RecordingOptions,
+    database: &Database,
+    recording_pads: &Arc<Mutex<HashMap<String, RecordingPads>>>,
+    events: &EventBus,
+) -> Result<RecordingStarted, RecordError> {
     let mut streams = streams.lock().await;
     let state = streams
         .get_mut(stream_id)
@@ -36,28 +296,69 @@ pub async fn start_recording_impl(
         .as_ref()
         .ok_or_else(|| RecordError::StreamError("Tee not initialized".to_string()))?;
 
+    let codec = state
+        .codec()
+        .ok_or_else(|| RecordError::StreamError("Stream codec not detected yet".to_string()))?;
+
     // --- 新しい録画Bin構築手順 ---
-    // 1. 各要素を生成
+    // 1. 各要素を生成（parserはmuxerが要求する形式への変換に使用）
     let queue = ElementFactory::make("queue").build()?;
-    let h264parse = ElementFactory::make("h264parse").build()?;
-    let mp4mux = ElementFactory::make("mp4mux").build()?;
-    mp4mux.set_property("faststart", true);
-    let filesink = ElementFactory::make("filesink").build()?;
-    filesink.set_property(
-        "location",
-        format!("/var/data/recordings/{}.mp4", recording_id),
-    );
-    filesink.set_property("sync", false);
-    filesink.set_property("async", false);
-    queue.set_property("max-size-buffers", 100u32);
-    queue.set_property("max-size-bytes", 0u32);
-    queue.set_property("max-size-time", 0u64);
+    let parser = codec.build_recording_parser()?;
+    let sink = match &options.segment {
+        Some(segment) => build_segment_sink(
+            stream_id,
+            recording_id,
+            &options.location,
+            options.container,
+            segment,
+            database,
+            events,
+        )?,
+        None => build_file_sink(&options.location, options.container)?,
+    };
+    let preroll = if options.preroll {
+        state.preroll.clone()
+    } else {
+        None
+    };
 
     // 2. Binを作成し要素を追加
     let recording_bin = Bin::new();
     recording_bin.set_property("name", format!("rec-bin-{}", recording_id));
-    recording_bin.add_many([&queue, &h264parse, &mp4mux, &filesink])?;
-    Element::link_many([&queue, &h264parse, &mp4mux, &filesink])?;
+    recording_bin.add_many([&queue, &parser])?;
+    recording_bin.add_many(&sink)?;
+    let mut chain = vec![&parser];
+    chain.extend(sink.iter());
+    Element::link_many(chain)?;
+
+    // プリロールがある場合は appsrc → concat の後にライブ映像（queue）が続くように接続する
+    let preroll_src = match preroll {
+        Some(_) => {
+            let appsrc = AppSrc::builder()
+                .format(gstreamer::Format::Time)
+                .is_live(false)
+                .max_bytes(0)
+                .build();
+            appsrc.set_property("handle-segment-change", true);
+            let concat = ElementFactory::make("concat")
+                .property("adjust-base", false)
+                .build()?;
+            recording_bin.add_many([appsrc.upcast_ref(), &concat])?;
+            // 先にリンクしたappsrc側のパッドが先に出力される
+            appsrc.link(&concat)?;
+            queue.link(&concat)?;
+            concat.link(&parser)?;
+            // プリロールを書き出す間、ライブ映像はqueueに溜める（書き出し後は通常の上限に戻す）
+            QueueLimits::PREROLL.apply(&queue);
+            Some(appsrc)
+        }
+        None => {
+            queue.link(&parser)?;
+            QueueLimits::LIVE.apply(&queue);
+            None
+        }
+    };
+    let mut restore_limits = vec![(queue.clone(), QueueLimits::LIVE)];
 
     // 3. GhostPadをqueueのsinkパッドでactive化してBinに追加
     let queue_sink_pad = queue
@@ -67,16 +368,136 @@ pub async fn start_recording_impl(
     ghost_sink.set_active(true)?;
     recording_bin.add_pad(&ghost_sink)?;
 
+    // 3-2. 音声がある場合は queue → parser → muxerの音声パッド を構築し、
+    //      GhostPad（audio_sink）として公開する
+    let audio = match state.audio_branch() {
+        Some((audio_tee, audio_codec)) => {
+            let audio_queue = ElementFactory::make("queue").build()?;
+            if preroll_src.is_some() {
+                // プリロールを書き出す間は映像と同様に溜める
+                restore_limits.push((audio_queue.clone(), QueueLimits::of(&audio_queue)));
+                QueueLimits::PREROLL.apply(&audio_queue);
+            }
+            let audio_parser = audio_codec.build_parser()?;
+            recording_bin.add_many([&audio_queue, &audio_parser])?;
+            audio_queue.link(&audio_parser)?;
+            let muxer_audio_pad = sink[0].request_pad_simple("audio_%u").ok_or_else(|| {
+                RecordError::StreamError(format!(
+                    "Container {:?} does not accept {} audio",
+                    options.container, audio_codec
+                ))
+            })?;
+            audio_parser
+                .static_pad("src")
+                .ok_or_else(|| {
+                    RecordError::StreamError("Failed to get audio parser src pad".to_string())
+                })?
+                .link(&muxer_audio_pad)?;
+            let audio_queue_sink_pad = audio_queue.static_pad("sink").ok_or_else(|| {
+                RecordError::StreamError("Failed to get audio queue sink pad".to_string())
+            })?;
+            let audio_ghost_sink = gstreamer::GhostPad::builder_with_target(&audio_queue_sink_pad)?
+                .name("audio_sink")
+                .build();
+            audio_ghost_sink.set_active(true)?;
+            recording_bin.add_pad(&audio_ghost_sink)?;
+            Some((audio_tee.clone(), audio_codec))
+        }
+        None => None,
+    };
+    if let Some(appsrc) = &preroll_src {
+        restore_limits_after_preroll(appsrc, restore_limits)?;
+    }
+
     // 4. Binをパイプラインに追加
     pipeline.add(&recording_bin)?;
 
+    // 5〜7で失敗した場合は、要求したteeのパッドとBinを取り除いてから返す
+    let mut tee_pads = Vec::new();
+    let attached = attach_recording_bin(
+        &recording_bin,
+        tee,
+        audio.as_ref().map(|(audio_tee, _)| audio_tee),
+        recording_id,
+        recording_pads,
+        &mut tee_pads,
+        preroll_src
+            .zip(preroll)
+            .map(|(appsrc, preroll)| (appsrc, preroll, &queue)),
+    )
+    .await;
+    let preroll_duration = match attached {
+        Ok(preroll_duration) => preroll_duration,
+        Err(e) => {
+            error!(
+                "[recording {}] Failed to start recording bin: {}",
+                recording_id, e
+            );
+            recording_pads
+                .lock()
+                .await
+                .remove(&recording_id.to_string());
+            discard_recording_bin(pipeline, &recording_bin, &tee_pads);
+            return Err(e);
+        }
+    };
+    info!(
+        "[recording {}] Pre-roll included: {} ms",
+        recording_id,
+        preroll_duration.mseconds()
+    );
+
+    state.recording_ids.push(recording_id.to_string());
+
+    // teeに流れている映像のcapsを録画の形式として記録する
+    let mut media = tee
+        .static_pad("sink")
+        .and_then(|pad| pad.current_caps())
+        .map(|caps| MediaInfo::from_caps(&caps))
+        .unwrap_or_default();
+    media.audio_codec = audio.map(|(_, audio_codec)| audio_codec.name().to_string());
+
+    Ok(RecordingStarted {
+        preroll: preroll_duration,
+        media,
+    })
+}
+
+/// teeのパッドを要求して録画Binとリンクし、プリロールを流し込んでPLAYINGにする。
+/// 要求したteeのパッドはtee_padsに追加する（失敗時の解放用）
+async fn attach_recording_bin(
+    recording_bin: &Bin,
+    tee: &Element,
+    audio_tee: Option<&Element>,
+    recording_id: Uuid,
+    recording_pads: &Arc<Mutex<HashMap<String, RecordingPads>>>,
+    tee_pads: &mut Vec<(Element, gstreamer::Pad)>,
+    preroll: Option<(AppSrc, PrerollBuffer, &Element)>,
+) -> Result<ClockTime, RecordError> {
     // 5. teeのsrcパッドとBinのsinkパッド（GhostPad）をリンク
     let tee_src_pad = tee
         .request_pad_simple("src_%u")
         .ok_or_else(|| RecordError::StreamError("Failed to request tee src pad".to_string()))?;
+    tee_pads.push((tee.clone(), tee_src_pad.clone()));
+    let audio_tee_src_pad = match audio_tee {
+        Some(audio_tee) => {
+            let pad = audio_tee.request_pad_simple("src_%u").ok_or_else(|| {
+                RecordError::StreamError("Failed to request audio tee src pad".to_string())
+            })?;
+            tee_pads.push((audio_tee.clone(), pad.clone()));
+            Some(pad)
+        }
+        None => None,
+    };
     {
         let mut pads = recording_pads.lock().await;
-        pads.insert(recording_id.to_string(), tee_src_pad.clone());
+        pads.insert(
+            recording_id.to_string(),
+            RecordingPads {
+                video: tee_src_pad.clone(),
+                audio: audio_tee_src_pad.clone(),
+            },
+        );
         info!(
             "Inserted tee_src_pad into recording_pads: recording_id={}",
             recording_id
@@ -89,18 +510,345 @@ pub async fn start_recording_impl(
         error!("Failed to link tee_src_pad to rec_bin_sink_pad: {}", e);
         RecordError::StreamError(format!("Failed to link tee_src_pad: {}", e))
     })?;
+    if let Some(audio_tee_src_pad) = &audio_tee_src_pad {
+        let rec_bin_audio_sink_pad = recording_bin.static_pad("audio_sink").ok_or_else(|| {
+            RecordError::StreamError("Failed to get recording_bin audio_sink pad".to_string())
+        })?;
+        audio_tee_src_pad.link(&rec_bin_audio_sink_pad)?;
+    }
+
+    // 6. プリロールを取り出してappsrcに流し込む（teeとのリンク後に取り出すことで隙間を作らない）
+    let preroll_duration = match preroll {
+        Some((appsrc, preroll, queue)) => push_preroll(&appsrc, &preroll, tee, queue)?,
+        None => ClockTime::ZERO,
+    };
 
-    // 6. Binの状態を親パイプラインと同期し、PLAYINGに遷移
+    // 7. Binの状態を親パイプラインと同期し、PLAYINGに遷移
     recording_bin.sync_children_states()?;
     recording_bin.set_state(State::Playing)?;
+    Ok(preroll_duration)
+}
 
-    // current_recording_idを必ずセット
-    state.current_recording_id = Some(recording_id.to_string());
-    state.is_recording = true;
+/// 開始や停止に失敗した録画Binをteeから切り離してパッドを解放し、パイプラインから取り除く
+pub fn discard_recording_bin(
+    pipeline: &gstreamer::Pipeline,
+    recording_bin: &Bin,
+    tee_pads: &[(Element, gstreamer::Pad)],
+) {
+    for (tee, pad) in tee_pads {
+        if let Some(peer) = pad.peer() {
+            let _ = pad.unlink(&peer);
+        }
+        tee.release_request_pad(pad);
+    }
+    let _ = recording_bin.set_state(State::Null);
+    let _ = pipeline.remove(recording_bin);
+}
 
+/// プリロールのEOSがappsrcから流れた時点で、ライブ映像を溜めていたqueueの上限を戻す
+fn restore_limits_after_preroll(
+    appsrc: &AppSrc,
+    limits: Vec<(Element, QueueLimits)>,
+) -> Result<(), RecordError> {
+    let src_pad = appsrc
+        .static_pad("src")
+        .ok_or_else(|| RecordError::StreamError("Failed to get appsrc src pad".to_string()))?;
+    src_pad.add_probe(PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+        match info.event() {
+            Some(event) if event.type_() == gstreamer::EventType::Eos => {
+                for (queue, limits) in &limits {
+                    limits.apply(queue);
+                }
+                PadProbeReturn::Remove
+            }
+            _ => PadProbeReturn::Ok,
+        }
+    });
     Ok(())
 }
 
+/// プリロールの内容をappsrcに書き込み、EOSで終端する。
+/// ライブ側のqueueからはプリロールと重複するバッファを取り除く。
+fn push_preroll(
+    appsrc: &AppSrc,
+    preroll: &PrerollBuffer,
+    tee: &Element,
+    queue: &Element,
+) -> Result<ClockTime, RecordError> {
+    let tee_sink_pad = tee
+        .static_pad("sink")
+        .ok_or_else(|| RecordError::StreamError("Failed to get tee sink pad".to_string()))?;
+    let snapshot = preroll.snapshot(&tee_sink_pad);
+
+    if let Some(snapshot) = &snapshot {
+        if let (Some(cutoff), Some(queue_src_pad)) = (snapshot.last_time(), queue.static_pad("src"))
+        {
+            queue_src_pad.add_probe(PadProbeType::BUFFER, move |_, info| {
+                match info.buffer().and_then(|buffer| buffer_time(buffer)) {
+                    Some(time) if time <= cutoff => PadProbeReturn::Drop,
+                    _ => PadProbeReturn::Remove,
+                }
+            });
+        }
+        for buffer in &snapshot.buffers {
+            let sample = gstreamer::Sample::builder()
+                .buffer(buffer)
+                .caps(&snapshot.caps)
+                .segment(&snapshot.segment)
+                .build();
+            appsrc.push_sample(&sample).map_err(|e| {
+                RecordError::StreamError(format!("Failed to push pre-roll buffer: {:?}", e))
+            })?;
+        }
+    }
+    appsrc
+        .end_of_stream()
+        .map_err(|e| RecordError::StreamError(format!("Failed to end pre-roll: {:?}", e)))?;
+
+    Ok(snapshot
+        .map(|snapshot| snapshot.duration())
+        .unwrap_or(ClockTime::ZERO))
+}
+
+/// コンテナ形式に対応するmuxerを生成
+pub fn build_muxer(container: RecordingContainer) -> Result<Element, RecordError> {
+    let muxer = match container {
+        RecordingContainer::Mp4 => ElementFactory::make("mp4mux")
+            .property("faststart", true)
+            .build()?,
+        // 1秒ごとにフラグメントを書き出し、異常終了時も書き込み済みの部分を再生可能にする
+        RecordingContainer::Fmp4 => ElementFactory::make("mp4mux")
+            .property("fragment-duration", 1000u32)
+            .build()?,
+        RecordingContainer::Mkv => ElementFactory::make("matroskamux").build()?,
+    };
+    Ok(muxer)
+}
+
+/// ファイル書き込み用のfilesinkを生成
+fn build_filesink() -> Result<Element, RecordError> {
+    let filesink = ElementFactory::make("filesink")
+        .name(RECORDING_SINK_NAME)
+        .property("sync", false)
+        .property("async", false)
+        .build()?;
+    Ok(filesink)
+}
+
+/// 単一ファイル録画用の muxer → filesink を生成
+fn build_file_sink(
+    location: &str,
+    container: RecordingContainer,
+) -> Result<Vec<Element>, RecordError> {
+    let muxer = build_muxer(container)?;
+    let filesink = build_filesink()?;
+    filesink.set_property("location", location);
+    Ok(vec![muxer, filesink])
+}
+
+/// セグメント録画用のsplitmuxsinkを生成し、セグメントの開始をDBに記録する
+fn build_segment_sink(
+    stream_id: &StreamId,
+    recording_id: Uuid,
+    directory: &str,
+    container: RecordingContainer,
+    segment: &SegmentOptions,
+    database: &Database,
+    events: &EventBus,
+) -> Result<Vec<Element>, RecordError> {
+    let muxer = build_muxer(container)?;
+    let filesink = build_filesink()?;
+    let splitmuxsink = ElementFactory::make("splitmuxsink")
+        .name(RECORDING_SPLITMUX_NAME)
+        .property("muxer", &muxer)
+        .property("sink", &filesink)
+        .property("send-keyframe-requests", false)
+        .build()?;
+    if let Some(seconds) = segment.max_duration_seconds {
+        let nanoseconds = seconds.checked_mul(1_000_000_000).ok_or_else(|| {
+            RecordError::StreamError(format!(
+                "segment_duration_seconds is too large: {}",
+                seconds
+            ))
+        })?;
+        splitmuxsink.set_property("max-size-time", nanoseconds);
+    }
+    if let Some(bytes) = segment.max_size_bytes {
+        splitmuxsink.set_property("max-size-bytes", bytes);
+    }
+
+    let tracker = spawn_segment_tracker(
+        database.clone(),
+        events.clone(),
+        stream_id.clone(),
+        recording_id,
+    );
+    let directory = PathBuf::from(directory);
+    splitmuxsink.connect("format-location-full", false, move |args| {
+        let fragment_id = args[1].get::<u32>().unwrap_or(0);
+        let file_name = format!("segment_{:05}.{}", fragment_id, container.extension());
+        let file_path = directory.join(&file_name).to_string_lossy().to_string();
+        info!(
+            "[recording {}] Opening segment {}: {}",
+            recording_id, fragment_id, file_path
+        );
+        let _ = tracker.send(SegmentOpened {
+            segment_index: fragment_id as i32,
+            file_name,
+            file_path: file_path.clone(),
+            start_time: Utc::now(),
+        });
+        Some(file_path.to_value())
+    });
+
+    Ok(vec![splitmuxsink])
+}
+
+/// セグメントの開始/終了をDBに順番に反映するタスクを起動する。
+/// 送信側（splitmuxsink）が破棄されると最後のセグメントを完了として記録する。
+fn spawn_segment_tracker(
+    database: Database,
+    events: EventBus,
+    stream_id: StreamId,
+    recording_id: Uuid,
+) -> mpsc::UnboundedSender<SegmentOpened> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<SegmentOpened>();
+    tokio::runtime::Handle::current().spawn(async move {
+        let mut previous: Option<(i32, String)> = None;
+        while let Some(opened) = rx.recv().await {
+            if let Some((index, path)) = previous.take() {
+                complete_segment(&database, recording_id, index, &path, opened.start_time).await;
+                events.publish(
+                    Some(&stream_id),
+                    EventKind::RecordingSegmentRolled {
+                        recording_id: recording_id.to_string(),
+                        segment_index: opened.segment_index,
+                        file_name: opened.file_name.clone(),
+                    },
+                );
+            }
+            match database
+                .create_segment(
+                    recording_id,
+                    opened.segment_index,
+                    opened.file_name,
+                    opened.file_path.clone(),
+                    opened.start_time,
+                )
+                .await
+            {
+                Ok(_) => previous = Some((opened.segment_index, opened.file_path)),
+                Err(e) => error!(
+                    "[recording {}] Failed to register segment {}: {}",
+                    recording_id, opened.segment_index, e
+                ),
+            }
+        }
+        if let Some((index, path)) = previous {
+            complete_segment(&database, recording_id, index, &path, Utc::now()).await;
+        }
+    });
+    tx
+}
+
+/// 未完了のセグメントを完了としてDBに記録する
+pub async fn complete_segment(
+    database: &Database,
+    recording_id: Uuid,
+    segment_index: i32,
+    file_path: &str,
+    end_time: DateTime<Utc>,
+) {
+    let file_size = file_size(Path::new(file_path));
+    if let Err(e) = database
+        .update_segment_completed(recording_id, segment_index, end_time, file_size)
+        .await
+    {
+        warn!(
+            "[recording {}] Failed to complete segment {}: {}",
+            recording_id, segment_index, e
+        );
+    }
+}
+
+/// 停止した録画のDBレコードを完了にする（未完了のセグメントも完了にする）
+pub async fn complete_recording(
+    database: &Database,
+    recording_id: Uuid,
+    end_time: DateTime<Utc>,
+    stop_reason: StopReason,
+) -> Result<Recording, RecordError> {
+    let recording = database.get_recording(recording_id).await?;
+    let duration = (end_time - recording.start_time).num_seconds();
+    // 一時停止中に停止した場合は一時停止区間を終了する
+    database.close_pause(recording_id, end_time).await?;
+    if recording.is_segmented() {
+        // 最後のセグメントを完了にする
+        for segment in database.list_segments(recording_id).await? {
+            if segment.end_time.is_none() {
+                complete_segment(
+                    database,
+                    recording_id,
+                    segment.segment_index,
+                    &segment.file_path,
+                    end_time,
+                )
+                .await;
+            }
+        }
+    }
+    let file_size = file_size(Path::new(&recording.file_path));
+    info!(
+        "[recording {}] Updating recording as completed: duration={}s, size={} bytes, reason={:?}",
+        recording_id, duration, file_size, stop_reason
+    );
+    database
+        .update_recording_completed(
+            recording_id,
+            end_time,
+            duration,
+            file_size,
+            Some(stop_reason),
+        )
+        .await
+}
+
+/// 確定に失敗した録画のDBレコードを失敗にする（一時停止中の場合は一時停止区間も終了する）
+pub async fn fail_recording(
+    database: &Database,
+    recording_id: Uuid,
+    end_time: DateTime<Utc>,
+    stop_reason: StopReason,
+) -> Result<Recording, RecordError> {
+    database.close_pause(recording_id, end_time).await?;
+    database
+        .update_recording_failed(recording_id, Some(end_time), None, None, Some(stop_reason))
+        .await
+}
+
+/// 録画ファイル（セグメント録画の場合はディレクトリ内のファイル合計）のサイズを返す
+pub fn file_size(path: &Path) -> i64 {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| file_size(&entry.path()))
+                    .sum()
+            })
+            .unwrap_or(0),
+        Ok(metadata) => metadata.len() as i64,
+        Err(e) => {
+            error!(
+                "Failed to get recording file metadata: {} (path={})",
+                e,
+                path.display()
+            );
+            0
+        }
+    }
+}
+
 // /// 録画停止ロジック
 // #[allow(dead_code)]
 // pub async fn stop_recording_impl(
@@ -172,3 +920,103 @@ pub async fn start_recording_impl(
 //     );
 //     Ok(recording_id)
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn context<'a>(label: Option<&'a str>, stream_name: Option<&'a str>) -> FileNameContext<'a> {
+        FileNameContext {
+            stream_id: "cam1",
+            stream_name,
+            recording_id: "rec-1",
+            label,
+            start_time: Local.with_ymd_and_hms(2026, 10, 16, 8, 30, 0).unwrap(),
+        }
+    }
+
+    fn render(template: &str, context: &FileNameContext<'_>) -> String {
+        render_file_stem(template, context)
+            .unwrap()
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        let stem = render(
+            "{yyyy}/{mm}/{dd}/{stream_name}_{label}_{datetime}_{recording_id}",
+            &context(Some("assembly"), Some("front door")),
+        );
+        assert_eq!(stem, "2026/10/16/front_door_assembly_20261016T083000_rec-1");
+    }
+
+    #[test]
+    fn label_cannot_traverse_directories() {
+        let stem = render(
+            "{label}_{recording_id}",
+            &context(Some("../../etc/passwd"), None),
+        );
+        assert_eq!(stem, "etc_passwd_rec-1");
+        assert!(!stem.contains('/'));
+
+        let stem = render("{label}/{recording_id}", &context(Some(".."), None));
+        assert_eq!(stem, "rec-1");
+    }
+
+    #[test]
+    fn dot_components_in_template_are_dropped() {
+        let stem = render("../{stream_id}/./{recording_id}", &context(None, None));
+        assert_eq!(stem, "cam1/rec-1");
+    }
+
+    #[test]
+    fn absolute_template_stays_relative() {
+        let stem = render("/{stream_id}/{recording_id}", &context(None, None));
+        assert_eq!(stem, "cam1/rec-1");
+    }
+
+    #[test]
+    fn empty_label_collapses_separators() {
+        let stem = render("{stream_id}_{label}_{recording_id}", &context(None, None));
+        assert_eq!(stem, "cam1_rec-1");
+
+        let stem = render(
+            "{stream_id}_{label}_{recording_id}",
+            &context(Some(""), None),
+        );
+        assert_eq!(stem, "cam1_rec-1");
+    }
+
+    #[test]
+    fn empty_directory_levels_are_skipped() {
+        let stem = render("{label}/{recording_id}", &context(None, None));
+        assert_eq!(stem, "rec-1");
+    }
+
+    #[test]
+    fn template_reducing_to_nothing_is_rejected() {
+        let result = render_file_stem("{label}", &context(Some("../"), None));
+        assert!(matches!(result, Err(RecordError::ConfigError(_))));
+
+        let result = render_file_stem("_/{label}/..", &context(None, None));
+        assert!(matches!(result, Err(RecordError::ConfigError(_))));
+    }
+
+    #[test]
+    fn invalid_placeholders_are_rejected() {
+        let result = render_file_stem("{unknown}", &context(None, None));
+        assert!(matches!(result, Err(RecordError::ConfigError(_))));
+
+        let result = render_file_stem("{label", &context(None, None));
+        assert!(matches!(result, Err(RecordError::ConfigError(_))));
+    }
+
+    #[test]
+    fn clean_component_trims_separators() {
+        assert_eq!(clean_component("__a___b__"), "a_b");
+        assert_eq!(clean_component(".._x_.."), "x");
+        assert_eq!(clean_component("..."), "");
+    }
+}
@@ -0,0 +1,763 @@
+use crate::app::AppState;
+use crate::error::RecordError;
+use crate::health::backoff_delay;
+use crate::models::{
+    RecordingSchedule, RecordingStatus, ScheduleKind, ScheduleRequest, StartRecordingRequest,
+    StopReason,
+};
+use chrono::{
+    DateTime, Datelike, Days, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// スケジュールを確認する間隔
+const SCHEDULER_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// catch_upが無効でも、録画枠の開始からこの時間以内であれば開始する
+const START_GRACE_SECONDS: i64 = 60;
+
+/// 存在しないローカル時刻の解釈に使うオフセットを取得する時刻（何時間前か）
+const DST_GAP_LOOKBACK_HOURS: i64 = 3;
+
+/// 録画枠の開始に失敗した場合の再試行の間隔（失敗するごとに倍にし、上限で頭打ちにする）
+const RETRY_INITIAL_SECONDS: u64 = 15;
+const RETRY_MAX_SECONDS: u64 = 300;
+
+/// 録画枠（開始〜終了）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// 現在の録画枠でスケジューラが開始した録画の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowRecording {
+    Active,
+    /// 手動で停止された、または停止条件に達した
+    Stopped,
+    /// サービスの再起動やストリームの途絶、録画の失敗で中断された
+    Interrupted,
+}
+
+/// 現在の録画枠に対する処理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowAction {
+    /// 処理済みの録画枠
+    Handled,
+    /// 開始から猶予を過ぎており、catch_upが無効なため開始しない
+    Skip,
+    Start,
+    /// 録画枠の途中で中断された録画に代わり、残りの時間を録画する
+    Restart,
+}
+
+/// 録画枠の開始に失敗したスケジュールの再試行の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    pub window_start: DateTime<Utc>,
+    pub failures: u32,
+    pub next_attempt: DateTime<Utc>,
+}
+
+impl Retry {
+    /// 失敗を記録し、次に試行する時刻を決める（録画枠が変わった場合は数え直す）
+    pub fn after_failure(
+        previous: Option<Retry>,
+        window_start: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Retry {
+        let failures = previous
+            .filter(|retry| retry.window_start == window_start)
+            .map_or(1, |retry| retry.failures.saturating_add(1));
+        let delay = backoff_delay(RETRY_INITIAL_SECONDS, RETRY_MAX_SECONDS, failures);
+        Retry {
+            window_start,
+            failures,
+            next_attempt: now + chrono::Duration::seconds(delay.as_secs() as i64),
+        }
+    }
+
+    /// 録画枠の開始を試行してよいか
+    pub fn is_due(&self, window_start: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        self.window_start != window_start || now >= self.next_attempt
+    }
+}
+
+/// 録画スケジュールを実行するバックグラウンドタスクを起動する
+pub fn spawn_scheduler(app_state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+        let mut first_run = true;
+        // 開始に失敗したスケジュールはバックオフして再試行する（スケジュールIDごと）
+        let mut retries = HashMap::new();
+        loop {
+            interval.tick().await;
+            if let Err(e) = run_schedules(&app_state, first_run, &mut retries).await {
+                error!("Failed to run recording schedules: {}", e);
+            }
+            first_run = false;
+        }
+    });
+}
+
+/// 有効な全スケジュールを確認し、開始すべき録画枠があれば録画を開始する
+async fn run_schedules(
+    app_state: &Arc<AppState>,
+    first_run: bool,
+    retries: &mut HashMap<Uuid, Retry>,
+) -> Result<(), RecordError> {
+    let now = Utc::now();
+    for schedule in app_state.database.list_enabled_schedules().await? {
+        if first_run {
+            report_missed_window(&schedule, now);
+        }
+
+        let window = match current_window(&schedule, now) {
+            Ok(Some(window)) => window,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("[schedule {}] Invalid schedule: {}", schedule.id, e);
+                continue;
+            }
+        };
+        let recording = if schedule.last_window_start == Some(window.start) {
+            window_recording_state(app_state, &schedule).await?
+        } else {
+            None
+        };
+        match window_action(&schedule, &window, now, recording) {
+            WindowAction::Handled => continue,
+            WindowAction::Skip => {
+                info!(
+                "[schedule {}] Skipping window already in progress ({} - {}), catch_up is disabled",
+                schedule.id, window.start, window.end
+            );
+                app_state
+                    .database
+                    .update_schedule_last_window(schedule.id, window.start, None)
+                    .await?;
+                continue;
+            }
+            WindowAction::Start => {}
+            WindowAction::Restart => info!(
+                "[schedule {}] Recording {:?} was interrupted, recording the rest of window {} - {}",
+                schedule.id, schedule.last_recording_id, window.start, window.end
+            ),
+        }
+        if let Some(retry) = retries.get(&schedule.id) {
+            if !retry.is_due(window.start, now) {
+                continue;
+            }
+        }
+
+        match start_window(app_state, &schedule, &window).await {
+            Ok(recording_id) => {
+                retries.remove(&schedule.id);
+                info!(
+                    "[schedule {}] Started recording {} for window {} - {}",
+                    schedule.id, recording_id, window.start, window.end
+                );
+                app_state
+                    .database
+                    .update_schedule_last_window(
+                        schedule.id,
+                        window.start,
+                        Uuid::parse_str(&recording_id).ok(),
+                    )
+                    .await?;
+            }
+            Err(e) => {
+                let retry =
+                    Retry::after_failure(retries.get(&schedule.id).copied(), window.start, now);
+                error!(
+                    "[schedule {}] Failed to start recording for window {} - {} (attempt {}), retrying at {}: {}",
+                    schedule.id, window.start, window.end, retry.failures, retry.next_attempt, e
+                );
+                retries.insert(schedule.id, retry);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 録画枠を開始するか判定する。recordingは処理済みの録画枠でスケジューラが開始した録画の状態
+pub fn window_action(
+    schedule: &RecordingSchedule,
+    window: &Window,
+    now: DateTime<Utc>,
+    recording: Option<WindowRecording>,
+) -> WindowAction {
+    if schedule.last_window_start == Some(window.start) {
+        return match recording {
+            Some(WindowRecording::Interrupted) => WindowAction::Restart,
+            _ => WindowAction::Handled,
+        };
+    }
+    let late_seconds = (now - window.start).num_seconds();
+    if late_seconds > START_GRACE_SECONDS && !schedule.catch_up {
+        return WindowAction::Skip;
+    }
+    WindowAction::Start
+}
+
+/// 処理済みの録画枠でスケジューラが開始した録画の状態を取得する
+async fn window_recording_state(
+    app_state: &Arc<AppState>,
+    schedule: &RecordingSchedule,
+) -> Result<Option<WindowRecording>, RecordError> {
+    let Some(recording_id) = schedule.last_recording_id else {
+        return Ok(None);
+    };
+    let active = app_state
+        .stream_manager
+        .find_recording_stream(&recording_id.to_string())
+        .await
+        .is_some();
+    if active {
+        return Ok(Some(WindowRecording::Active));
+    }
+    let recording = app_state.database.get_recording(recording_id).await?;
+    // 停止処理中（ファイルの確定待ち）の録画はまだRECORDINGのまま
+    if recording.status == RecordingStatus::Recording {
+        return Ok(Some(WindowRecording::Active));
+    }
+    Ok(Some(ended_recording(recording.stop_reason)))
+}
+
+/// 終了した録画の停止理由から状態を判定する。停止理由がない場合は失敗した録画
+pub fn ended_recording(stop_reason: Option<StopReason>) -> WindowRecording {
+    match stop_reason {
+        Some(StopReason::StreamLost | StopReason::ServiceRestart) | None => {
+            WindowRecording::Interrupted
+        }
+        Some(_) => WindowRecording::Stopped,
+    }
+}
+
+/// 停止中に終了した録画枠があれば警告する
+fn report_missed_window(schedule: &RecordingSchedule, now: DateTime<Utc>) {
+    if let Some(window) = missed_window(schedule, now) {
+        warn!(
+            "[schedule {}] Missed recording window {} - {} while the service was stopped",
+            schedule.id, window.start, window.end
+        );
+    }
+}
+
+/// スケジュール作成後に終了し、処理されなかった直近の録画枠
+pub fn missed_window(schedule: &RecordingSchedule, now: DateTime<Utc>) -> Option<Window> {
+    let window = previous_window(schedule, now).ok()??;
+    let handled = schedule
+        .last_window_start
+        .is_some_and(|last| last >= window.start);
+    (!handled && window.start >= schedule.created_at).then_some(window)
+}
+
+/// 必要であればストリームに接続し、録画枠の終了時刻で停止する録画を開始する
+async fn start_window(
+    app_state: &Arc<AppState>,
+    schedule: &RecordingSchedule,
+    window: &Window,
+) -> Result<String, RecordError> {
+    let profile = app_state.database.get_profile(schedule.profile_id).await?;
+    let stream_id = app_state.connect_profile(&profile).await?;
+
+    let response = app_state
+        .start_recording(
+            &stream_id,
+            StartRecordingRequest {
+                filename: schedule.label.clone(),
+                stop_at: Some(window.end),
+                ..Default::default()
+            },
+        )
+        .await?;
+    Ok(response.recording_id)
+}
+
+/// nowを含む録画枠を返す
+pub fn current_window(
+    schedule: &RecordingSchedule,
+    now: DateTime<Utc>,
+) -> Result<Option<Window>, RecordError> {
+    let windows = candidate_windows(schedule, now)?;
+    Ok(windows
+        .into_iter()
+        .filter(|window| window.start <= now && now < window.end)
+        .max_by_key(|window| window.start))
+}
+
+/// now以前に終了した直近の録画枠を返す
+pub fn previous_window(
+    schedule: &RecordingSchedule,
+    now: DateTime<Utc>,
+) -> Result<Option<Window>, RecordError> {
+    let windows = candidate_windows(schedule, now)?;
+    Ok(windows
+        .into_iter()
+        .filter(|window| window.end <= now)
+        .max_by_key(|window| window.end))
+}
+
+/// now付近（過去1週間程度）の録画枠を列挙する
+fn candidate_windows(
+    schedule: &RecordingSchedule,
+    now: DateTime<Utc>,
+) -> Result<Vec<Window>, RecordError> {
+    let tz = parse_timezone(&schedule.timezone)?;
+    match schedule.kind {
+        ScheduleKind::Weekly => {
+            let (Some(start_time), Some(end_time)) = (schedule.start_time, schedule.end_time)
+            else {
+                return Err(RecordError::ConfigError(
+                    "Weekly schedule requires start_time and end_time".to_string(),
+                ));
+            };
+            let today = now.with_timezone(&tz).date_naive();
+            Ok((0..=7)
+                .filter_map(|offset| today.checked_sub_days(Days::new(offset)))
+                .filter(|date| {
+                    schedule
+                        .weekdays
+                        .contains(&(date.weekday().number_from_monday() as i16))
+                })
+                .filter_map(|date| weekly_window(&tz, date, start_time, end_time))
+                .collect())
+        }
+        ScheduleKind::Cron => {
+            let expression = schedule.cron_expression.as_deref().unwrap_or_default();
+            let cron = parse_cron(expression)?;
+            let duration = chrono::Duration::seconds(schedule.duration_seconds.unwrap_or(0));
+            let now_tz = now.with_timezone(&tz);
+            // 直近の発火時刻から遡り、終了済みの録画枠が1つ見つかるまで列挙する。
+            // rev()は起点より前の時刻を返すため、nowちょうどの発火を含むよう1秒後から遡る
+            let mut windows = Vec::new();
+            let from = now_tz + chrono::Duration::seconds(1);
+            for start in cron.after(&from).rev().take(64) {
+                let window = Window {
+                    start: start.with_timezone(&Utc),
+                    end: (start + duration).with_timezone(&Utc),
+                };
+                windows.push(window);
+                if window.end <= now {
+                    break;
+                }
+            }
+            Ok(windows)
+        }
+    }
+}
+
+/// 指定日の開始時刻から終了時刻までの録画枠（終了時刻が開始時刻以前なら翌日に終了）
+fn weekly_window(
+    tz: &Tz,
+    date: NaiveDate,
+    start_time: NaiveTime,
+    end_time: NaiveTime,
+) -> Option<Window> {
+    let end_date = if end_time <= start_time {
+        date.checked_add_days(Days::new(1))?
+    } else {
+        date
+    };
+    Some(Window {
+        start: resolve_local(tz, date.and_time(start_time))?,
+        end: resolve_local(tz, end_date.and_time(end_time))?,
+    })
+}
+
+/// ローカル時刻をUTCに変換する。夏時間の切り替えで重複する時刻は早い方、
+/// 存在しない時刻は切り替え前のオフセットで解釈する（02:30 → 03:30のように後ろにずれる）
+fn resolve_local(tz: &Tz, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+    if let Some(time) = tz.from_local_datetime(&local).earliest() {
+        return Some(time.with_timezone(&Utc));
+    }
+    let before = tz
+        .from_local_datetime(&(local - chrono::Duration::hours(DST_GAP_LOOKBACK_HOURS)))
+        .earliest()?;
+    let offset = chrono::Duration::seconds(before.offset().fix().local_minus_utc() as i64);
+    Some(Utc.from_utc_datetime(&(local - offset)))
+}
+
+/// スケジュール作成・更新リクエストを検証する
+pub fn validate_schedule(request: &ScheduleRequest) -> Result<(), RecordError> {
+    if let Some(timezone) = &request.timezone {
+        parse_timezone(timezone).map_err(|e| RecordError::StreamError(e.to_string()))?;
+    }
+    match request.kind {
+        ScheduleKind::Weekly => {
+            if request.weekdays.is_empty()
+                || request.weekdays.iter().any(|day| !(1..=7).contains(day))
+            {
+                return Err(RecordError::StreamError(
+                    "weekdays must contain ISO weekdays between 1 (Mon) and 7 (Sun)".to_string(),
+                ));
+            }
+            match (request.start_time, request.end_time) {
+                (Some(start), Some(end)) if start != end => Ok(()),
+                _ => Err(RecordError::StreamError(
+                    "Weekly schedule requires different start_time and end_time".to_string(),
+                )),
+            }
+        }
+        ScheduleKind::Cron => {
+            let expression = request.cron_expression.as_deref().ok_or_else(|| {
+                RecordError::StreamError("Cron schedule requires cron_expression".to_string())
+            })?;
+            parse_cron(expression).map_err(|e| RecordError::StreamError(e.to_string()))?;
+            if request.duration_seconds.unwrap_or(0) <= 0 {
+                return Err(RecordError::StreamError(
+                    "Cron schedule requires duration_seconds greater than 0".to_string(),
+                ));
+            }
+            Ok(())
+        }
+    }
+}
+
+fn parse_timezone(timezone: &str) -> Result<Tz, RecordError> {
+    Tz::from_str(timezone)
+        .map_err(|_| RecordError::ConfigError(format!("Unknown timezone: {}", timezone)))
+}
+
+fn parse_cron(expression: &str) -> Result<cron::Schedule, RecordError> {
+    cron::Schedule::from_str(expression).map_err(|e| {
+        RecordError::ConfigError(format!("Invalid cron expression '{}': {}", expression, e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn time(value: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(value, "%H:%M").unwrap()
+    }
+
+    fn schedule(kind: ScheduleKind, timezone: &str) -> RecordingSchedule {
+        RecordingSchedule {
+            id: Uuid::nil(),
+            profile_id: Uuid::nil(),
+            name: "test".to_string(),
+            kind,
+            weekdays: Vec::new(),
+            start_time: None,
+            end_time: None,
+            cron_expression: None,
+            duration_seconds: None,
+            timezone: timezone.to_string(),
+            label: None,
+            enabled: true,
+            catch_up: true,
+            last_window_start: None,
+            last_recording_id: None,
+            created_at: utc("2026-01-01T00:00:00Z"),
+            updated_at: utc("2026-01-01T00:00:00Z"),
+        }
+    }
+
+    fn weekly(weekdays: &[i16], start: &str, end: &str, timezone: &str) -> RecordingSchedule {
+        RecordingSchedule {
+            weekdays: weekdays.to_vec(),
+            start_time: Some(time(start)),
+            end_time: Some(time(end)),
+            ..schedule(ScheduleKind::Weekly, timezone)
+        }
+    }
+
+    fn cron(expression: &str, duration_seconds: i64) -> RecordingSchedule {
+        RecordingSchedule {
+            cron_expression: Some(expression.to_string()),
+            duration_seconds: Some(duration_seconds),
+            ..schedule(ScheduleKind::Cron, "UTC")
+        }
+    }
+
+    fn window(start: &str, end: &str) -> Window {
+        Window {
+            start: utc(start),
+            end: utc(end),
+        }
+    }
+
+    #[test]
+    fn weekly_window_contains_now() {
+        // 2026-10-12は月曜日
+        let schedule = weekly(&[1], "09:00", "17:00", "UTC");
+        assert_eq!(
+            current_window(&schedule, utc("2026-10-12T10:00:00Z")).unwrap(),
+            Some(window("2026-10-12T09:00:00Z", "2026-10-12T17:00:00Z"))
+        );
+        assert_eq!(
+            current_window(&schedule, utc("2026-10-12T17:00:00Z")).unwrap(),
+            None
+        );
+        assert_eq!(
+            current_window(&schedule, utc("2026-10-13T10:00:00Z")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn weekly_window_uses_schedule_timezone() {
+        let schedule = weekly(&[1], "09:00", "10:00", "Asia/Tokyo");
+        assert_eq!(
+            current_window(&schedule, utc("2026-10-12T00:30:00Z")).unwrap(),
+            Some(window("2026-10-12T00:00:00Z", "2026-10-12T01:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn weekly_window_crosses_midnight() {
+        // 金曜日22:00から土曜日02:00まで
+        let schedule = weekly(&[5], "22:00", "02:00", "UTC");
+        let expected = Some(window("2026-10-16T22:00:00Z", "2026-10-17T02:00:00Z"));
+        assert_eq!(
+            current_window(&schedule, utc("2026-10-16T23:00:00Z")).unwrap(),
+            expected
+        );
+        assert_eq!(
+            current_window(&schedule, utc("2026-10-17T01:59:59Z")).unwrap(),
+            expected
+        );
+        assert_eq!(
+            current_window(&schedule, utc("2026-10-17T02:00:00Z")).unwrap(),
+            None
+        );
+        // 土曜日の深夜は枠に含まれない
+        assert_eq!(
+            current_window(&schedule, utc("2026-10-17T23:00:00Z")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn weekly_window_crossing_the_week_boundary() {
+        // 日曜日22:00から月曜日06:00まで。月曜日の朝は前日（7日前を含む範囲）の枠に含まれる
+        let schedule = weekly(&[7], "22:00", "06:00", "UTC");
+        assert_eq!(
+            current_window(&schedule, utc("2026-10-12T05:00:00Z")).unwrap(),
+            Some(window("2026-10-11T22:00:00Z", "2026-10-12T06:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn weekly_window_in_dst_gap_is_shifted_forward() {
+        // 2026-03-08（日曜日）のニューヨークでは02:00〜03:00が存在しない
+        let schedule = weekly(&[7], "02:30", "04:00", "America/New_York");
+        assert_eq!(
+            current_window(&schedule, utc("2026-03-08T07:45:00Z")).unwrap(),
+            Some(window("2026-03-08T07:30:00Z", "2026-03-08T08:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn weekly_window_in_dst_overlap_uses_earliest() {
+        // 2026-11-01（日曜日）のニューヨークでは01:00〜02:00が2回ある
+        let schedule = weekly(&[7], "01:30", "03:00", "America/New_York");
+        assert_eq!(
+            current_window(&schedule, utc("2026-11-01T06:00:00Z")).unwrap(),
+            Some(window("2026-11-01T05:30:00Z", "2026-11-01T08:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn previous_weekly_window() {
+        let schedule = weekly(&[1, 3], "09:00", "17:00", "UTC");
+        assert_eq!(
+            previous_window(&schedule, utc("2026-10-15T08:00:00Z")).unwrap(),
+            Some(window("2026-10-14T09:00:00Z", "2026-10-14T17:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn cron_window_contains_now() {
+        let schedule = cron("0 0 * * * *", 1800);
+        assert_eq!(
+            current_window(&schedule, utc("2026-10-16T10:10:00Z")).unwrap(),
+            Some(window("2026-10-16T10:00:00Z", "2026-10-16T10:30:00Z"))
+        );
+        assert_eq!(
+            current_window(&schedule, utc("2026-10-16T10:40:00Z")).unwrap(),
+            None
+        );
+        assert_eq!(
+            previous_window(&schedule, utc("2026-10-16T10:40:00Z")).unwrap(),
+            Some(window("2026-10-16T10:00:00Z", "2026-10-16T10:30:00Z"))
+        );
+    }
+
+    #[test]
+    fn cron_window_starts_at_fire_time() {
+        let schedule = cron("0 0 * * * *", 1800);
+        assert_eq!(
+            current_window(&schedule, utc("2026-10-16T10:00:00Z")).unwrap(),
+            Some(window("2026-10-16T10:00:00Z", "2026-10-16T10:30:00Z"))
+        );
+    }
+
+    #[test]
+    fn overlapping_cron_windows_use_latest_start() {
+        let schedule = cron("0 0 * * * *", 7200);
+        assert_eq!(
+            current_window(&schedule, utc("2026-10-16T10:10:00Z")).unwrap(),
+            Some(window("2026-10-16T10:00:00Z", "2026-10-16T12:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn invalid_schedules_are_errors() {
+        let mut schedule = weekly(&[1], "09:00", "17:00", "Mars/Olympus");
+        assert!(current_window(&schedule, utc("2026-10-12T10:00:00Z")).is_err());
+        schedule.timezone = "UTC".to_string();
+        schedule.start_time = None;
+        assert!(current_window(&schedule, utc("2026-10-12T10:00:00Z")).is_err());
+        assert!(current_window(&cron("not a cron", 60), utc("2026-10-12T10:00:00Z")).is_err());
+    }
+
+    #[test]
+    fn window_action_respects_grace_and_catch_up() {
+        let window = window("2026-10-12T09:00:00Z", "2026-10-12T17:00:00Z");
+        let mut schedule = weekly(&[1], "09:00", "17:00", "UTC");
+
+        let on_time = utc("2026-10-12T09:00:30Z");
+        let late = utc("2026-10-12T09:05:00Z");
+        assert_eq!(
+            window_action(&schedule, &window, on_time, None),
+            WindowAction::Start
+        );
+        assert_eq!(
+            window_action(&schedule, &window, late, None),
+            WindowAction::Start
+        );
+
+        schedule.catch_up = false;
+        assert_eq!(
+            window_action(&schedule, &window, on_time, None),
+            WindowAction::Start
+        );
+        assert_eq!(
+            window_action(&schedule, &window, late, None),
+            WindowAction::Skip
+        );
+
+        schedule.last_window_start = Some(window.start);
+        assert_eq!(
+            window_action(&schedule, &window, on_time, None),
+            WindowAction::Handled
+        );
+    }
+
+    #[test]
+    fn interrupted_recording_is_restarted_inside_the_window() {
+        let window = window("2026-10-12T09:00:00Z", "2026-10-12T17:00:00Z");
+        let mut schedule = weekly(&[1], "09:00", "17:00", "UTC");
+        schedule.catch_up = false;
+        schedule.last_window_start = Some(window.start);
+        schedule.last_recording_id = Some(Uuid::nil());
+        let now = utc("2026-10-12T12:00:00Z");
+
+        // 再起動後の整合性チェックで回復した録画、ストリームの途絶、失敗した録画
+        for stop_reason in [
+            Some(StopReason::ServiceRestart),
+            Some(StopReason::StreamLost),
+            None,
+        ] {
+            assert_eq!(
+                window_action(&schedule, &window, now, Some(ended_recording(stop_reason))),
+                WindowAction::Restart
+            );
+        }
+
+        // 録画中、または手動停止・停止条件による終了は再開しない
+        assert_eq!(
+            window_action(&schedule, &window, now, Some(WindowRecording::Active)),
+            WindowAction::Handled
+        );
+        for stop_reason in [StopReason::Manual, StopReason::Schedule] {
+            assert_eq!(
+                window_action(
+                    &schedule,
+                    &window,
+                    now,
+                    Some(ended_recording(Some(stop_reason)))
+                ),
+                WindowAction::Handled
+            );
+        }
+
+        // catch_upが無効で開始しなかった録画枠
+        assert_eq!(
+            window_action(&schedule, &window, now, None),
+            WindowAction::Handled
+        );
+    }
+
+    #[test]
+    fn failed_window_start_backs_off() {
+        let window_start = utc("2026-10-12T09:00:00Z");
+        let now = utc("2026-10-12T09:00:10Z");
+
+        let first = Retry::after_failure(None, window_start, now);
+        assert_eq!(first.failures, 1);
+        assert_eq!(first.next_attempt, utc("2026-10-12T09:00:25Z"));
+        assert!(!first.is_due(window_start, utc("2026-10-12T09:00:24Z")));
+        assert!(first.is_due(window_start, utc("2026-10-12T09:00:25Z")));
+
+        let second = Retry::after_failure(Some(first), window_start, first.next_attempt);
+        assert_eq!(second.failures, 2);
+        assert_eq!(second.next_attempt, utc("2026-10-12T09:00:55Z"));
+
+        // 間隔は上限で頭打ちになる
+        let mut retry = second;
+        for _ in 0..10 {
+            retry = Retry::after_failure(Some(retry), window_start, retry.next_attempt);
+        }
+        let capped = Retry::after_failure(Some(retry), window_start, retry.next_attempt);
+        assert_eq!(
+            capped.next_attempt - retry.next_attempt,
+            chrono::Duration::seconds(300)
+        );
+
+        // 次の録画枠はすぐに試行し、失敗回数も数え直す
+        let next_window = utc("2026-10-13T09:00:00Z");
+        assert!(retry.is_due(next_window, next_window));
+        assert_eq!(
+            Retry::after_failure(Some(retry), next_window, next_window).failures,
+            1
+        );
+    }
+
+    #[test]
+    fn missed_window_is_reported_once() {
+        let mut schedule = weekly(&[1], "09:00", "17:00", "UTC");
+        let now = utc("2026-10-12T18:00:00Z");
+        assert_eq!(
+            missed_window(&schedule, now),
+            Some(window("2026-10-12T09:00:00Z", "2026-10-12T17:00:00Z"))
+        );
+
+        schedule.last_window_start = Some(utc("2026-10-12T09:00:00Z"));
+        assert_eq!(missed_window(&schedule, now), None);
+    }
+
+    #[test]
+    fn windows_before_the_schedule_existed_are_not_missed() {
+        let mut schedule = weekly(&[1], "09:00", "17:00", "UTC");
+        schedule.created_at = utc("2026-10-12T12:00:00Z");
+        assert_eq!(missed_window(&schedule, utc("2026-10-12T18:00:00Z")), None);
+    }
+}
@@ -0,0 +1,184 @@
+use crate::models::StreamStatsSample;
+use crate::recording::MediaInfo;
+use chrono::Utc;
+use gstreamer::prelude::*;
+use gstreamer::{Bin, BufferFlags, BufferRef, CapsRef, ClockTime, Element};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// 保持する統計の履歴の数（1秒ごとに5分間）
+pub const STATS_HISTORY_LENGTH: usize = 300;
+
+/// teeに届いた映像の累積値（teeのsinkパッドのプローブで更新する）
+#[derive(Debug, Default)]
+struct Counters {
+    bytes: u64,
+    frames: u64,
+    last_keyframe: Option<ClockTime>,
+    keyframe_interval: Option<ClockTime>,
+}
+
+/// rtspsrcのjitterbufferの統計の合計
+#[derive(Debug, Default, Clone, Copy)]
+struct RtpCounters {
+    pushed: u64,
+    lost: u64,
+}
+
+#[derive(Debug, Default)]
+struct Sampler {
+    /// 前回の集計時刻と、その時点の累積バイト数・フレーム数
+    previous: Option<(Instant, u64, u64)>,
+    /// 前回の集計時点のjitterbufferの値（rtspsrcを作り直すと0から数え直しになる）
+    previous_rtp: RtpCounters,
+    /// 接続してからのRTPパケットの累計
+    total_rtp: RtpCounters,
+    history: VecDeque<StreamStatsSample>,
+}
+
+/// ストリームの受信状況の統計（ビットレート、フレームレート、パケットロスなど）と直近の履歴
+#[derive(Debug, Clone, Default)]
+pub struct StreamStats {
+    counters: Arc<Mutex<Counters>>,
+    sampler: Arc<Mutex<Sampler>>,
+}
+
+impl StreamStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// teeに届いたバッファを集計する
+    pub fn record_buffer(&self, buffer: &BufferRef) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.bytes += buffer.size() as u64;
+        counters.frames += 1;
+        if !buffer.flags().contains(BufferFlags::DELTA_UNIT) {
+            if let Some(pts) = buffer.pts() {
+                if let Some(last) = counters.last_keyframe {
+                    counters.keyframe_interval = pts.checked_sub(last);
+                }
+                counters.last_keyframe = Some(pts);
+            }
+        }
+    }
+
+    /// 前回の集計からの差分で統計を1件作成し、履歴に追加する。
+    /// srcはrtspsrc（jitterbufferの統計の取得元）、capsはteeに流れている映像のcaps
+    pub fn sample(&self, src: Option<&Element>, caps: Option<&CapsRef>) -> StreamStatsSample {
+        let (bytes, frames, keyframe_interval) = {
+            let counters = self.counters.lock().unwrap();
+            (counters.bytes, counters.frames, counters.keyframe_interval)
+        };
+        let (rtp, jitter) = src.map(jitterbuffer_stats).unwrap_or_default();
+        let media = caps.map(MediaInfo::from_caps).unwrap_or_default();
+
+        let now = Instant::now();
+        let mut sampler = self.sampler.lock().unwrap();
+        let (bitrate_bps, fps) = match sampler.previous {
+            Some((at, previous_bytes, previous_frames)) => {
+                let seconds = now.duration_since(at).as_secs_f64().max(f64::EPSILON);
+                (
+                    ((bytes - previous_bytes) as f64 * 8.0 / seconds) as u64,
+                    (frames - previous_frames) as f64 / seconds,
+                )
+            }
+            None => (0, 0.0),
+        };
+        sampler.previous = Some((now, bytes, frames));
+
+        let pushed = counter_delta(rtp.pushed, sampler.previous_rtp.pushed);
+        let lost = counter_delta(rtp.lost, sampler.previous_rtp.lost);
+        sampler.previous_rtp = rtp;
+        sampler.total_rtp.pushed += pushed;
+        sampler.total_rtp.lost += lost;
+
+        let sample = StreamStatsSample {
+            timestamp: Utc::now(),
+            bitrate_bps,
+            fps,
+            keyframe_interval_ms: keyframe_interval.map(|interval| interval.mseconds()),
+            width: media.width,
+            height: media.height,
+            packets_received: sampler.total_rtp.pushed,
+            packets_lost: sampler.total_rtp.lost,
+            packet_loss_percent: if pushed + lost > 0 {
+                lost as f64 * 100.0 / (pushed + lost) as f64
+            } else {
+                0.0
+            },
+            jitter_ms: jitter,
+        };
+        if sampler.history.len() >= STATS_HISTORY_LENGTH {
+            sampler.history.pop_front();
+        }
+        sampler.history.push_back(sample.clone());
+        sample
+    }
+
+    pub fn latest(&self) -> Option<StreamStatsSample> {
+        self.sampler.lock().unwrap().history.back().cloned()
+    }
+
+    /// 古い順の履歴
+    pub fn history(&self) -> Vec<StreamStatsSample> {
+        self.sampler
+            .lock()
+            .unwrap()
+            .history
+            .iter()
+            .cloned()
+            .collect()
+    }
+}
+
+/// 累積値の差分。rtspsrcを作り直して値が0に戻った場合は現在値を差分とする
+fn counter_delta(current: u64, previous: u64) -> u64 {
+    current.checked_sub(previous).unwrap_or(current)
+}
+
+/// rtspsrc内の全jitterbufferの受信・ロスしたパケット数の合計と、最大の平均ジッタ（ミリ秒）
+fn jitterbuffer_stats(src: &Element) -> (RtpCounters, Option<f64>) {
+    let mut counters = RtpCounters::default();
+    let mut jitter: Option<f64> = None;
+    let Some(bin) = src.downcast_ref::<Bin>() else {
+        return (counters, jitter);
+    };
+    for element in bin.iterate_recurse().into_iter().flatten() {
+        let is_jitterbuffer = element
+            .factory()
+            .is_some_and(|factory| factory.name() == "rtpjitterbuffer");
+        if !is_jitterbuffer {
+            continue;
+        }
+        let stats = element.property::<gstreamer::Structure>("stats");
+        counters.pushed += stats.get::<u64>("num-pushed").unwrap_or(0);
+        counters.lost += stats.get::<u64>("num-lost").unwrap_or(0);
+        if let Ok(avg_jitter) = stats.get::<u64>("avg-jitter") {
+            let avg_jitter_ms = avg_jitter as f64 / 1_000_000.0;
+            jitter = Some(jitter.map_or(avg_jitter_ms, |max| max.max(avg_jitter_ms)));
+        }
+    }
+    (counters, jitter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delta_of_an_increasing_counter() {
+        assert_eq!(counter_delta(150, 100), 50);
+        assert_eq!(counter_delta(100, 100), 0);
+    }
+
+    #[test]
+    fn reset_or_wrapped_counter_counts_from_zero() {
+        // rtspsrcを作り直して0から数え直した場合
+        assert_eq!(counter_delta(20, 100), 20);
+        assert_eq!(counter_delta(0, 100), 0);
+        // 上限で一周した場合も負の差分にはせず、現在値を差分とする
+        assert_eq!(counter_delta(5, u64::MAX), 5);
+    }
+}
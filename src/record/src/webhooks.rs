@@ -0,0 +1,480 @@
+use crate::config::WebhookConfig;
+use crate::credentials::{CredentialCipher, Password};
+use crate::database::Database;
+use crate::error::RecordError;
+use crate::events::{Event, EventBus, EventKind};
+use crate::health::backoff_delay;
+use crate::models::{Webhook, WebhookDelivery, WebhookDeliveryStatus, WebhookEventType};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// 署名のヘッダー（sha256=HMAC-SHA256("{timestamp}.{body}")の16進数）
+pub const SIGNATURE_HEADER: &str = "X-Record-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Record-Timestamp";
+pub const EVENT_HEADER: &str = "X-Record-Event";
+pub const DELIVERY_HEADER: &str = "X-Record-Delivery";
+
+/// ストリームと録画のイベントを購読しているWebhookに配信する
+pub struct WebhookDispatcher {
+    config: WebhookConfig,
+    database: Database,
+    credentials: CredentialCipher,
+    client: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    pub fn new(
+        config: WebhookConfig,
+        database: Database,
+        credentials: CredentialCipher,
+    ) -> Result<Self, RecordError> {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(
+                config.timeout_seconds.max(1),
+            ))
+            .build()
+            .map_err(|e| {
+                RecordError::InternalError(format!("Failed to create webhook client: {}", e))
+            })?;
+        Ok(Self {
+            config,
+            database,
+            credentials,
+            client,
+        })
+    }
+
+    /// 配信のバックグラウンドタスクを起動する。
+    /// 起動前や負荷の高い間のイベントを取りこぼさないよう、呼び出し時に上限のないキューで購読を開始する
+    pub fn spawn(self: &Arc<Self>, events: &EventBus) {
+        let dispatcher = self.clone();
+        let mut receiver = events.subscribe_lossless();
+        tokio::spawn(async move {
+            // 再起動前に完了しなかった配信を再開
+            match dispatcher.database.list_pending_webhook_deliveries().await {
+                Ok(deliveries) => {
+                    if !deliveries.is_empty() {
+                        info!("Resuming {} pending webhook deliveries", deliveries.len());
+                    }
+                    for delivery in deliveries {
+                        dispatcher.spawn_delivery(delivery);
+                    }
+                }
+                Err(e) => error!("Failed to load pending webhook deliveries: {}", e),
+            }
+
+            while let Some(event) = receiver.recv().await {
+                if let Err(e) = dispatcher.dispatch(&event).await {
+                    error!("Failed to dispatch webhook event: {}", e);
+                }
+            }
+        });
+    }
+
+    /// イベントを購読しているWebhookごとに配信を登録して送信する（登録の失敗は他のWebhookに影響しない）
+    async fn dispatch(self: &Arc<Self>, event: &Event) -> Result<(), RecordError> {
+        let Some((event_type, data)) = self.event_data(event).await else {
+            return Ok(());
+        };
+        let webhooks = self
+            .database
+            .list_subscribed_webhooks(event_type.as_str(), event.stream_id.as_deref())
+            .await?;
+        for webhook in webhooks {
+            match self
+                .create_delivery(
+                    &webhook,
+                    event_type,
+                    event.stream_id.as_deref(),
+                    event.timestamp,
+                    data.clone(),
+                )
+                .await
+            {
+                Ok(delivery) => self.spawn_delivery(delivery),
+                Err(e) => error!(
+                    "[webhook {}] Failed to create {} delivery: {}",
+                    webhook.id,
+                    event_type.as_str(),
+                    e
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// 通知の対象となるイベントの種類と、ペイロードのdata
+    async fn event_data(&self, event: &Event) -> Option<(WebhookEventType, serde_json::Value)> {
+        let event_type = webhook_event_type(&event.kind)?;
+        let data = match &event.kind {
+            EventKind::RecordingStopped {
+                recording_id,
+                stop_reason,
+            } => {
+                let recording = self.recording(recording_id).await;
+                let segments = self.segments(recording_id).await;
+                json!({
+                    "recording_id": recording_id,
+                    "stop_reason": stop_reason,
+                    "recording": recording,
+                    "segments": segments,
+                })
+            }
+            EventKind::RecordingFailed {
+                recording_id,
+                message,
+            } => {
+                let recording = self.recording(recording_id).await;
+                json!({
+                    "recording_id": recording_id,
+                    "message": message,
+                    "recording": recording,
+                })
+            }
+            EventKind::StreamConnected => json!({ "reconnected": false }),
+            EventKind::StreamReconnected => json!({ "reconnected": true }),
+            EventKind::StreamReconnecting { reason, .. } => {
+                json!({ "reason": reason, "reconnecting": true })
+            }
+            EventKind::StreamFailed { reason } => {
+                json!({ "reason": reason, "reconnecting": false })
+            }
+            _ => return None,
+        };
+        Some((event_type, data))
+    }
+
+    /// ペイロードに含める録画の情報（取得できない場合はnull）
+    async fn recording(&self, recording_id: &str) -> serde_json::Value {
+        let Ok(id) = Uuid::parse_str(recording_id) else {
+            return serde_json::Value::Null;
+        };
+        match self.database.get_recording(id).await {
+            Ok(recording) => serde_json::to_value(recording).unwrap_or_default(),
+            Err(e) => {
+                warn!(
+                    "[recording {}] Failed to load recording for webhook: {}",
+                    recording_id, e
+                );
+                serde_json::Value::Null
+            }
+        }
+    }
+
+    /// セグメント録画の各ファイル（単一ファイルの録画の場合は空）
+    async fn segments(&self, recording_id: &str) -> serde_json::Value {
+        let Ok(id) = Uuid::parse_str(recording_id) else {
+            return json!([]);
+        };
+        match self.database.list_segments(id).await {
+            Ok(segments) => serde_json::to_value(segments).unwrap_or_default(),
+            Err(e) => {
+                warn!(
+                    "[recording {}] Failed to load segments for webhook: {}",
+                    recording_id, e
+                );
+                json!([])
+            }
+        }
+    }
+
+    async fn create_delivery(
+        &self,
+        webhook: &Webhook,
+        event_type: WebhookEventType,
+        stream_id: Option<&str>,
+        timestamp: DateTime<Utc>,
+        data: serde_json::Value,
+    ) -> Result<WebhookDelivery, RecordError> {
+        let id = Uuid::new_v4();
+        let payload = json!({
+            "id": id,
+            "event": event_type,
+            "timestamp": timestamp,
+            "stream_id": stream_id,
+            "data": data,
+        });
+        self.database
+            .create_webhook_delivery(id, webhook.id, event_type.as_str(), &payload)
+            .await
+    }
+
+    /// 購読にかかわらず指定したWebhookにpingを送信する
+    pub async fn ping(self: &Arc<Self>, webhook: &Webhook) -> Result<WebhookDelivery, RecordError> {
+        let delivery = self
+            .create_delivery(
+                webhook,
+                WebhookEventType::Ping,
+                webhook.stream_id.as_deref(),
+                Utc::now(),
+                json!({ "webhook_id": webhook.id }),
+            )
+            .await?;
+        self.spawn_delivery(delivery.clone());
+        Ok(delivery)
+    }
+
+    fn spawn_delivery(self: &Arc<Self>, delivery: WebhookDelivery) {
+        let dispatcher = self.clone();
+        tokio::spawn(async move {
+            let delivery_id = delivery.id;
+            if let Err(e) = dispatcher.deliver(delivery).await {
+                error!("[webhook delivery {}] Delivery aborted: {}", delivery_id, e);
+            }
+        });
+    }
+
+    /// 成功するか最大試行回数に達するまで、バックオフを挟んで送信する
+    async fn deliver(&self, mut delivery: WebhookDelivery) -> Result<(), RecordError> {
+        let config = &self.config;
+        let database = &self.database;
+        while delivery.status == WebhookDeliveryStatus::Pending {
+            if let Some(at) = delivery.next_attempt_at {
+                if let Ok(delay) = (at - Utc::now()).to_std() {
+                    tokio::time::sleep(delay).await;
+                }
+            }
+
+            // 待機中に変更・削除された場合に備えて、試行のたびに最新の設定を読み込む
+            let webhook = match database.get_webhook(delivery.webhook_id).await {
+                Ok(webhook) => webhook,
+                // 削除された場合は配信履歴も削除されている
+                Err(RecordError::NotFound(_)) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            let attempts = delivery.attempts + 1;
+            if !webhook.enabled {
+                database
+                    .update_webhook_delivery(
+                        delivery.id,
+                        WebhookDeliveryStatus::Failed,
+                        delivery.attempts,
+                        delivery.response_status,
+                        Some("Webhook is disabled"),
+                        None,
+                    )
+                    .await?;
+                return Ok(());
+            }
+
+            let (response_status, result) = self.send(&webhook, &delivery).await;
+            let (status, last_error, next_attempt_at) = match result {
+                Ok(()) => (WebhookDeliveryStatus::Succeeded, None, None),
+                Err(e) if attempts as u32 >= config.max_attempts.max(1) => {
+                    warn!(
+                        "[webhook {}] Delivery {} failed after {} attempt(s): {}",
+                        webhook.id, delivery.id, attempts, e
+                    );
+                    (WebhookDeliveryStatus::Failed, Some(e), None)
+                }
+                Err(e) => {
+                    let delay = backoff_delay(
+                        config.initial_backoff_seconds,
+                        config.max_backoff_seconds,
+                        attempts as u32,
+                    );
+                    warn!(
+                        "[webhook {}] Delivery {} failed (attempt {}), retrying in {} s: {}",
+                        webhook.id,
+                        delivery.id,
+                        attempts,
+                        delay.as_secs(),
+                        e
+                    );
+                    let next_attempt_at = chrono::Duration::from_std(delay)
+                        .ok()
+                        .map(|delay| Utc::now() + delay);
+                    (WebhookDeliveryStatus::Pending, Some(e), next_attempt_at)
+                }
+            };
+            delivery = database
+                .update_webhook_delivery(
+                    delivery.id,
+                    status,
+                    attempts,
+                    response_status.map(i32::from),
+                    last_error.as_deref(),
+                    next_attempt_at,
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// 1回送信する。2xx以外の応答は失敗とする
+    async fn send(
+        &self,
+        webhook: &Webhook,
+        delivery: &WebhookDelivery,
+    ) -> (Option<u16>, Result<(), String>) {
+        let secret = match webhook
+            .secret_encrypted
+            .as_deref()
+            .map(|secret| self.credentials.decrypt(secret))
+            .transpose()
+        {
+            Ok(secret) => secret,
+            Err(e) => return (None, Err(e.to_string())),
+        };
+        let body = match serde_json::to_vec(&delivery.payload) {
+            Ok(body) => body,
+            Err(e) => return (None, Err(e.to_string())),
+        };
+        let timestamp = Utc::now().timestamp().to_string();
+
+        let mut request = self
+            .client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, &delivery.event_type)
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .header(TIMESTAMP_HEADER, &timestamp);
+        if let Some(secret) = &secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, &timestamp, &body));
+        }
+
+        match request.body(body).send().await {
+            Ok(response) => {
+                let status = response.status();
+                if status.is_success() {
+                    info!(
+                        "[webhook {}] Delivered {} ({})",
+                        webhook.id, delivery.event_type, delivery.id
+                    );
+                    (Some(status.as_u16()), Ok(()))
+                } else {
+                    (
+                        Some(status.as_u16()),
+                        Err(format!("Unexpected response status: {}", status)),
+                    )
+                }
+            }
+            Err(e) => (None, Err(e.to_string())),
+        }
+    }
+}
+
+/// 通知の対象となるイベントの種類（対象外のイベントはNone）
+fn webhook_event_type(kind: &EventKind) -> Option<WebhookEventType> {
+    match kind {
+        EventKind::RecordingStopped { .. } => Some(WebhookEventType::RecordingCompleted),
+        EventKind::RecordingFailed { .. } => Some(WebhookEventType::RecordingFailed),
+        EventKind::StreamConnected | EventKind::StreamReconnected => {
+            Some(WebhookEventType::StreamConnected)
+        }
+        // 途絶えた時点（最初の再接続の試行）のみ通知する
+        EventKind::StreamReconnecting { attempt, .. } if *attempt == 1 => {
+            Some(WebhookEventType::StreamLost)
+        }
+        EventKind::StreamFailed { .. } => Some(WebhookEventType::StreamLost),
+        _ => None,
+    }
+}
+
+/// 受信側で検証する署名（sha256=の後にHMAC-SHA256の16進数）
+fn sign(secret: &Password, timestamp: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::StopReason;
+
+    #[test]
+    fn signature_is_hmac_sha256_of_timestamp_and_body() {
+        let body = br#"{"event":"ping"}"#;
+        assert_eq!(
+            sign(&Password::new("secret"), "1700000000", body),
+            "sha256=4d39bd2442f073b6bc62e95d0297ce25475582a17389ab860abdc778fe1d9f77"
+        );
+        assert_eq!(
+            sign(&Password::new("other"), "1700000000", body),
+            "sha256=0b745d77e8146ca45a844ad89177fce05067c8ba592d8a848a1837088acdd617"
+        );
+        // タイムスタンプも署名の対象
+        assert_ne!(
+            sign(&Password::new("secret"), "1700000001", body),
+            sign(&Password::new("secret"), "1700000000", body)
+        );
+    }
+
+    #[test]
+    fn only_notified_events_have_a_webhook_event_type() {
+        let recording_id = "f47ac10b-58cc-4372-a567-0e02b2c3d479".to_string();
+        let cases = [
+            (
+                EventKind::RecordingStopped {
+                    recording_id: recording_id.clone(),
+                    stop_reason: StopReason::Manual,
+                },
+                Some(WebhookEventType::RecordingCompleted),
+            ),
+            (
+                EventKind::RecordingFailed {
+                    recording_id: recording_id.clone(),
+                    message: "failed".to_string(),
+                },
+                Some(WebhookEventType::RecordingFailed),
+            ),
+            (
+                EventKind::StreamConnected,
+                Some(WebhookEventType::StreamConnected),
+            ),
+            (
+                EventKind::StreamReconnected,
+                Some(WebhookEventType::StreamConnected),
+            ),
+            (
+                EventKind::StreamReconnecting {
+                    attempt: 1,
+                    reason: "timeout".to_string(),
+                },
+                Some(WebhookEventType::StreamLost),
+            ),
+            // 2回目以降の再接続の試行は通知しない
+            (
+                EventKind::StreamReconnecting {
+                    attempt: 2,
+                    reason: "timeout".to_string(),
+                },
+                None,
+            ),
+            (
+                EventKind::StreamFailed {
+                    reason: "timeout".to_string(),
+                },
+                Some(WebhookEventType::StreamLost),
+            ),
+            (EventKind::StreamStalled, None),
+            (EventKind::StreamDisconnected, None),
+            (
+                EventKind::RecordingStarted {
+                    recording_id: recording_id.clone(),
+                },
+                None,
+            ),
+            (
+                EventKind::RecordingSegmentRolled {
+                    recording_id,
+                    segment_index: 1,
+                    file_name: "segment_00001.mp4".to_string(),
+                },
+                None,
+            ),
+        ];
+        for (kind, expected) in cases {
+            assert_eq!(webhook_event_type(&kind), expected, "{:?}", kind);
+        }
+    }
+}
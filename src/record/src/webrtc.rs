@@ -1,13 +1,65 @@
+use crate::codec::{AudioCodec, VideoCodec};
+use crate::config::WebRtcConfig;
 use crate::error::RecordError;
+use crate::stream::build_viewer_queue;
 use gstreamer::prelude::*;
-use gstreamer::{Element, ElementFactory};
+use gstreamer::{Element, ElementFactory, Pad, Pipeline, Promise, Structure, StructureRef};
+use gstreamer_sdp::SDPMessage;
+use gstreamer_webrtc::{
+    WebRTCICEGatheringState, WebRTCRTPTransceiver, WebRTCRTPTransceiverDirection, WebRTCSDPType,
+    WebRTCSessionDescription,
+};
+use std::ops::Deref;
+use std::str::FromStr;
+use std::time::Duration;
+use uuid::Uuid;
 
-/// WebRTCストリーム開始処理
+/// ICE候補の収集の完了を確認する間隔
+const ICE_GATHERING_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 視聴セッションのwebrtcbinの名前の接頭辞（バスのエラーを受信側のエラーと区別する）
+pub const WEBRTC_SESSION_PREFIX: &str = "webrtc-session-";
+
+/// WHIPで受信するwebrtcbinの名前
+const WHIP_SOURCE_NAME: &str = "whip-src";
+
+/// WHIPで受信できる映像のRTPのcaps（録画・配信できるコーデック）
+const WHIP_VIDEO_CAPS: &str =
+    "application/x-rtp, media=video, encoding-name=H264, clock-rate=90000; \
+     application/x-rtp, media=video, encoding-name=H265, clock-rate=90000; \
+     application/x-rtp, media=video, encoding-name=AV1, clock-rate=90000";
+
+/// WHIPで受信できる音声のRTPのcaps
+const WHIP_AUDIO_CAPS: &str =
+    "application/x-rtp, media=audio, encoding-name=OPUS, clock-rate=48000";
+
+/// WebRTCの視聴セッション（WHEPのセッションリソース）。
+/// 映像・音声ごとにtee→queue→payloader→webrtcbinの分岐を持つ
+#[derive(Debug, Clone)]
+pub struct WebRtcSession {
+    pub id: String,
+    pub webrtcbin: Element,
+    branches: Vec<WebRtcBranch>,
+    /// オファーのm-lineのmid（trickle ICEの候補をm-lineの番号に対応付ける）
+    mids: Vec<Option<String>>,
+}
+
+#[derive(Debug, Clone)]
+struct WebRtcBranch {
+    tee_pad: Pad,
+    queue: Element,
+    payloader: Element,
+}
+
+/// WebRTCストリーム開始処理（teeからwebrtcbinへの分岐を作成する）
 pub async fn start_webrtc_streaming_impl(
     is_connected: bool,
-    pipeline: Option<&gstreamer::Pipeline>,
+    pipeline: Option<&Pipeline>,
     tee: Option<&Element>,
-) -> Result<Element, RecordError> {
+    codec: Option<VideoCodec>,
+    audio: Option<(&Element, AudioCodec)>,
+    config: &WebRtcConfig,
+) -> Result<WebRtcSession, RecordError> {
     if !is_connected {
         return Err(RecordError::StreamError("Stream not connected".to_string()));
     }
@@ -15,43 +67,271 @@ pub async fn start_webrtc_streaming_impl(
         pipeline.ok_or_else(|| RecordError::StreamError("Pipeline not initialized".to_string()))?;
     let tee =
         tee.ok_or_else(|| RecordError::StreamError("Tee element not initialized".to_string()))?;
+    let codec = codec
+        .ok_or_else(|| RecordError::StreamError("Stream codec not detected yet".to_string()))?;
 
-    // queueとwebrtcbinを作成
-    let queue = ElementFactory::make("queue")
-        .build()
-        .map_err(|_| RecordError::StreamError("Failed to create queue".to_string()))?;
+    let id = Uuid::new_v4().to_string();
+    let webrtcbin = build_webrtcbin(&format!("{}{}", WEBRTC_SESSION_PREFIX, id), config)?;
+    pipeline
+        .add(&webrtcbin)
+        .map_err(|_| RecordError::StreamError("Failed to add webrtcbin to pipeline".to_string()))?;
+    webrtcbin.sync_state_with_parent().ok();
+
+    let mut session = WebRtcSession {
+        id,
+        webrtcbin,
+        branches: Vec::new(),
+        mids: Vec::new(),
+    };
+    if let Err(e) = add_branches(pipeline, &mut session, tee, codec, audio) {
+        teardown_session(pipeline, &session);
+        return Err(e);
+    }
+    Ok(session)
+}
+
+/// WHIPで配信者から受信するwebrtcbinを作成してパイプラインに追加する。
+/// 受信したパッドはpad-addedで下流（identity、音声用queue）にリンクする
+pub fn build_ingest_session(
+    pipeline: &Pipeline,
+    config: &WebRtcConfig,
+) -> Result<WebRtcSession, RecordError> {
+    let webrtcbin = build_webrtcbin(WHIP_SOURCE_NAME, config)?;
+    // 受信専用のトランシーバーを用意し、アンサーのコーデックを録画できるものに限定する
+    for caps in [WHIP_VIDEO_CAPS, WHIP_AUDIO_CAPS] {
+        let caps = gstreamer::Caps::from_str(caps)
+            .map_err(|e| RecordError::InternalError(format!("Invalid WHIP caps: {}", e)))?;
+        webrtcbin.emit_by_name::<WebRTCRTPTransceiver>(
+            "add-transceiver",
+            &[&WebRTCRTPTransceiverDirection::Recvonly, &caps],
+        );
+    }
+    pipeline
+        .add(&webrtcbin)
+        .map_err(|_| RecordError::StreamError("Failed to add webrtcbin to pipeline".to_string()))?;
+    Ok(WebRtcSession {
+        id: Uuid::new_v4().to_string(),
+        webrtcbin,
+        branches: Vec::new(),
+        mids: Vec::new(),
+    })
+}
+
+/// STUN/TURNサーバーを設定したwebrtcbinを作成
+fn build_webrtcbin(name: &str, config: &WebRtcConfig) -> Result<Element, RecordError> {
     let webrtcbin = ElementFactory::make("webrtcbin")
+        .name(name)
+        .property_from_str("bundle-policy", "max-bundle")
         .build()
         .map_err(|_| RecordError::StreamError("Failed to create webrtcbin".to_string()))?;
+    if let Some(stun_server) = &config.stun_server {
+        webrtcbin.set_property("stun-server", stun_server);
+    }
+    for turn_server in &config.turn_servers {
+        if !webrtcbin.emit_by_name::<bool>("add-turn-server", &[turn_server]) {
+            return Err(RecordError::ConfigError(format!(
+                "Invalid TURN server: {}",
+                turn_server
+            )));
+        }
+    }
+    Ok(webrtcbin)
+}
+
+/// 映像と音声（ある場合）の分岐を作成してwebrtcbinにリンク
+fn add_branches(
+    pipeline: &Pipeline,
+    session: &mut WebRtcSession,
+    tee: &Element,
+    codec: VideoCodec,
+    audio: Option<(&Element, AudioCodec)>,
+) -> Result<(), RecordError> {
+    let payloader = codec.build_webrtc_payloader()?;
+    let branch = add_branch(pipeline, tee, payloader, &session.webrtcbin)?;
+    session.branches.push(branch);
+
+    // 音声がある場合はOpusのトラックを追加
+    if let Some((audio_tee, audio_codec)) = audio {
+        let audio_payloader = audio_codec.build_webrtc_payloader()?;
+        let branch = add_branch(pipeline, audio_tee, audio_payloader, &session.webrtcbin)?;
+        session.branches.push(branch);
+    }
+    Ok(())
+}
 
-    // pipelineに追加
+/// tee→queue→payloader→webrtcbinを作成してリンク
+fn add_branch(
+    pipeline: &Pipeline,
+    tee: &Element,
+    payloader: Element,
+    webrtcbin: &Element,
+) -> Result<WebRtcBranch, RecordError> {
+    let queue = build_viewer_queue(None)?;
     pipeline
-        .add_many([&queue, &webrtcbin])
+        .add_many([&queue, &payloader])
         .map_err(|_| RecordError::StreamError("Failed to add elements to pipeline".to_string()))?;
     queue.sync_state_with_parent().ok();
-    webrtcbin.sync_state_with_parent().ok();
+    payloader.sync_state_with_parent().ok();
 
     // Teeのsrc padをrequestし、queueにリンク
-    let tee_src_pad = tee
-        .request_pad_simple("src_%u")
-        .ok_or_else(|| RecordError::StreamError("Failed to request tee src pad".to_string()))?;
-    let queue_sink_pad = queue
+    let Some(tee_pad) = tee.request_pad_simple("src_%u") else {
+        for element in [&queue, &payloader] {
+            let _ = element.set_state(gstreamer::State::Null);
+            let _ = pipeline.remove(element);
+        }
+        return Err(RecordError::StreamError(
+            "Failed to request tee src pad".to_string(),
+        ));
+    };
+    let branch = WebRtcBranch {
+        tee_pad,
+        queue,
+        payloader,
+    };
+    if let Err(e) = link_branch(&branch, webrtcbin) {
+        remove_branch(pipeline, &branch);
+        return Err(e);
+    }
+    Ok(branch)
+}
+
+fn link_branch(branch: &WebRtcBranch, webrtcbin: &Element) -> Result<(), RecordError> {
+    let queue_sink_pad = branch
+        .queue
         .static_pad("sink")
         .ok_or_else(|| RecordError::StreamError("Failed to get queue sink pad".to_string()))?;
-    tee_src_pad
+    branch
+        .tee_pad
         .link(&queue_sink_pad)
         .map_err(|e| RecordError::StreamError(format!("Failed to link tee to queue: {}", e)))?;
 
-    // queue→webrtcbinをリンク
-    let queue_src_pad = queue
+    // queue→payloader→webrtcbinをリンク
+    branch.queue.link(&branch.payloader).map_err(|e| {
+        RecordError::StreamError(format!("Failed to link queue to payloader: {}", e))
+    })?;
+    let payloader_src_pad = branch
+        .payloader
         .static_pad("src")
-        .ok_or_else(|| RecordError::StreamError("Failed to get queue src pad".to_string()))?;
-    let webrtcbin_sink_pad = webrtcbin.static_pad("sink_video_rtp").ok_or_else(|| {
-        RecordError::StreamError("Failed to get webrtcbin sink_video_rtp pad".to_string())
+        .ok_or_else(|| RecordError::StreamError("Failed to get payloader src pad".to_string()))?;
+    let webrtcbin_sink_pad = webrtcbin.request_pad_simple("sink_%u").ok_or_else(|| {
+        RecordError::StreamError("Failed to request webrtcbin sink pad".to_string())
     })?;
-    queue_src_pad.link(&webrtcbin_sink_pad).map_err(|e| {
-        RecordError::StreamError(format!("Failed to link queue to webrtcbin: {}", e))
+    payloader_src_pad.link(&webrtcbin_sink_pad).map_err(|e| {
+        RecordError::StreamError(format!("Failed to link payloader to webrtcbin: {}", e))
     })?;
+    Ok(())
+}
 
-    Ok(webrtcbin)
+/// オファーを適用してアンサーを作成する。
+/// アンサーにはICE候補を含める（収集がice_gathering_timeoutまでに終わらない場合は収集済みの候補のみ）
+pub async fn negotiate(
+    session: &mut WebRtcSession,
+    offer_sdp: &str,
+    ice_gathering_timeout: Duration,
+) -> Result<String, RecordError> {
+    let offer = SDPMessage::parse_buffer(offer_sdp.as_bytes())
+        .map_err(|_| RecordError::StreamError("Invalid SDP offer".to_string()))?;
+    session.mids = offer
+        .medias()
+        .map(|media| media.attribute_val("mid").map(str::to_string))
+        .collect();
+    let offer = WebRTCSessionDescription::new(WebRTCSDPType::Offer, offer);
+    let webrtcbin = &session.webrtcbin;
+
+    let (promise, reply) = Promise::new_future();
+    webrtcbin.emit_by_name::<()>("set-remote-description", &[&offer, &promise]);
+    promise_reply(reply.await, "set remote description")?;
+
+    let (promise, reply) = Promise::new_future();
+    webrtcbin.emit_by_name::<()>("create-answer", &[&None::<gstreamer::Structure>, &promise]);
+    let answer = promise_reply(reply.await, "create answer")?
+        .and_then(|reply| reply.get::<WebRTCSessionDescription>("answer").ok())
+        .ok_or_else(|| RecordError::StreamError("Failed to create SDP answer".to_string()))?;
+
+    let (promise, reply) = Promise::new_future();
+    webrtcbin.emit_by_name::<()>("set-local-description", &[&answer, &promise]);
+    promise_reply(reply.await, "set local description")?;
+
+    let _ = tokio::time::timeout(ice_gathering_timeout, async {
+        while webrtcbin.property::<WebRTCICEGatheringState>("ice-gathering-state")
+            != WebRTCICEGatheringState::Complete
+        {
+            tokio::time::sleep(ICE_GATHERING_POLL_INTERVAL).await;
+        }
+    })
+    .await;
+
+    let local = webrtcbin
+        .property::<Option<WebRTCSessionDescription>>("local-description")
+        .unwrap_or(answer);
+    local
+        .sdp()
+        .as_text()
+        .map_err(|e| RecordError::StreamError(format!("Failed to serialize SDP answer: {}", e)))
+}
+
+/// Promiseの応答。webrtcbinがエラーを返した場合はエラーにする
+fn promise_reply<R: Deref<Target = StructureRef>>(
+    reply: Result<Option<R>, gstreamer::PromiseError>,
+    action: &str,
+) -> Result<Option<Structure>, RecordError> {
+    let reply =
+        reply.map_err(|e| RecordError::StreamError(format!("Failed to {}: {:?}", action, e)))?;
+    let Some(reply) = reply else {
+        return Ok(None);
+    };
+    if let Ok(error) = reply.get::<glib::Error>("error") {
+        return Err(RecordError::StreamError(format!(
+            "Failed to {}: {}",
+            action, error
+        )));
+    }
+    Ok(Some(reply.to_owned()))
+}
+
+/// trickle ICEのSDPフラグメント（application/trickle-ice-sdpfrag）の候補を追加する。
+/// 追加した候補の数を返す
+pub fn add_ice_candidates(session: &WebRtcSession, sdpfrag: &str) -> usize {
+    let mut mline_index = 0u32;
+    let mut added = 0;
+    for line in sdpfrag.lines().map(str::trim) {
+        if let Some(mid) = line.strip_prefix("a=mid:") {
+            mline_index = session
+                .mids
+                .iter()
+                .position(|m| m.as_deref() == Some(mid))
+                .unwrap_or(0) as u32;
+        } else if let Some(candidate) = line.strip_prefix("a=") {
+            if candidate.starts_with("candidate:") {
+                session
+                    .webrtcbin
+                    .emit_by_name::<()>("add-ice-candidate", &[&mline_index, &candidate]);
+                added += 1;
+            }
+        }
+    }
+    added
+}
+
+/// セッションの分岐をteeから切り離し、要素を停止してパイプラインから削除する
+pub fn teardown_session(pipeline: &Pipeline, session: &WebRtcSession) {
+    for branch in &session.branches {
+        remove_branch(pipeline, branch);
+    }
+    let _ = session.webrtcbin.set_state(gstreamer::State::Null);
+    let _ = pipeline.remove(&session.webrtcbin);
+}
+
+/// teeのパッドを切り離して解放し、queueとpayloaderを削除する（teeはallow-not-linkedのため止まらない）
+fn remove_branch(pipeline: &Pipeline, branch: &WebRtcBranch) {
+    if let Some(peer) = branch.tee_pad.peer() {
+        let _ = branch.tee_pad.unlink(&peer);
+    }
+    for element in [&branch.queue, &branch.payloader] {
+        let _ = element.set_state(gstreamer::State::Null);
+        let _ = pipeline.remove(element);
+    }
+    if let Some(tee) = branch.tee_pad.parent_element() {
+        tee.release_request_pad(&branch.tee_pad);
+    }
 }